@@ -0,0 +1,261 @@
+use num::Integer;
+use std::{collections::HashMap, hash::Hash};
+
+/**
+ * Shape of the sequence `start, step(start), step(step(start)), ...` once it
+ * starts repeating: the first `prefix` states are never seen again, after
+ * which the states repeat every `period` steps.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Cycle {
+    pub(crate) prefix: usize,
+    pub(crate) period: usize,
+}
+
+impl Cycle {
+    /**
+     * The earliest step whose state is the same as the state after `n` steps
+     */
+    pub(crate) fn reduce(&self, n: usize) -> usize {
+        if n < self.prefix {
+            n
+        } else {
+            self.prefix + (n - self.prefix) % self.period
+        }
+    }
+}
+
+/**
+ * Brent's algorithm: only ever holds two states, and calls `step` fewer times
+ * than Floyd's. Never returns if the sequence does not repeat.
+ */
+#[allow(dead_code)]
+pub(crate) fn brent<S, F>(start: &S, mut step: F) -> Cycle
+where
+    S: Clone + PartialEq,
+    F: FnMut(&S) -> S,
+{
+    let mut power = 1;
+    let mut period = 1;
+    let mut tortoise = start.clone();
+    let mut hare = step(start);
+    while tortoise != hare {
+        if power == period {
+            tortoise = hare.clone();
+            power *= 2;
+            period = 0;
+        }
+        hare = step(&hare);
+        period += 1;
+    }
+
+    let mut tortoise = start.clone();
+    let mut hare = start.clone();
+    for _ in 0..period {
+        hare = step(&hare);
+    }
+    let mut prefix = 0;
+    while tortoise != hare {
+        tortoise = step(&tortoise);
+        hare = step(&hare);
+        prefix += 1;
+    }
+    Cycle { prefix, period }
+}
+
+/**
+ * Floyd's tortoise and hare. Never returns if the sequence does not repeat.
+ */
+#[allow(dead_code)]
+pub(crate) fn floyd<S, F>(start: &S, mut step: F) -> Cycle
+where
+    S: Clone + PartialEq,
+    F: FnMut(&S) -> S,
+{
+    let mut tortoise = step(start);
+    let mut hare = step(&tortoise);
+    while tortoise != hare {
+        tortoise = step(&tortoise);
+        hare = step(&hare);
+        hare = step(&hare);
+    }
+
+    let mut prefix = 0;
+    let mut tortoise = start.clone();
+    while tortoise != hare {
+        tortoise = step(&tortoise);
+        hare = step(&hare);
+        prefix += 1;
+    }
+
+    let mut period = 1;
+    let mut hare = step(&tortoise);
+    while tortoise != hare {
+        hare = step(&hare);
+        period += 1;
+    }
+    Cycle { prefix, period }
+}
+
+/**
+ * State after `n` steps, found by running the sequence only as far as the
+ * first time that state appears
+ */
+#[allow(dead_code)]
+pub(crate) fn state_after<S, F>(start: S, mut step: F, n: usize) -> S
+where
+    S: Clone + PartialEq,
+    F: FnMut(&S) -> S,
+{
+    let cycle = brent(&start, &mut step);
+    (0..cycle.reduce(n)).fold(start, |s, _| step(&s))
+}
+
+/**
+ * Every distinct state of the sequence in order, found by hashing each state
+ * until one repeats. Uses more memory than `brent` but steps each state only
+ * once, and any step can be looked up afterwards.
+ */
+#[derive(Debug, Clone)]
+pub(crate) struct History<S> {
+    pub(crate) cycle: Cycle,
+    pub(crate) states: Vec<S>,
+}
+
+impl<S: Clone + Eq + Hash> History<S> {
+    pub(crate) fn find<F>(start: S, mut step: F) -> Self
+    where
+        F: FnMut(&S) -> S,
+    {
+        let mut seen: HashMap<S, usize> = Default::default();
+        let mut states = vec![start];
+        loop {
+            let current = states.last().unwrap();
+            if let Some(&prefix) = seen.get(current) {
+                states.pop();
+                let period = states.len() - prefix;
+                return Self {
+                    cycle: Cycle { prefix, period },
+                    states,
+                };
+            }
+            seen.insert(current.clone(), states.len() - 1);
+            let next = step(current);
+            states.push(next);
+        }
+    }
+
+    #[allow(dead_code)]
+    pub(crate) fn state_at(&self, n: usize) -> &S {
+        &self.states[self.cycle.reduce(n)]
+    }
+}
+
+/**
+ * The numbers that are `a.0` mod `a.1` and also `b.0` mod `b.1`, as a single
+ * residue and modulus, for lining up two cycles. The moduli don't need to be
+ * coprime. `None` if no number fits both, and an error if the combined
+ * modulus doesn't fit in a `u64`.
+ */
+pub(crate) fn crt(a: (u64, u64), b: (u64, u64)) -> anyhow::Result<Option<(u64, u64)>> {
+    let (m1, m2) = (a.1 as i128, b.1 as i128);
+    let (r1, r2) = (a.0 as i128 % m1, b.0 as i128 % m2);
+    let egcd = m1.extended_gcd(&m2);
+    let g = egcd.gcd;
+    if (r2 - r1) % g != 0 {
+        return Ok(None);
+    }
+    // m1 * k = r2 - r1 (mod m2), and x is the inverse of m1 / g mod m2 / g
+    let step = m2 / g;
+    let diff = ((r2 - r1) / g).rem_euclid(step) as u128;
+    let k = (diff * egcd.x.rem_euclid(step) as u128 % step as u128) as i128;
+    let overflow = || anyhow::anyhow!("lining up mod {} and mod {} overflows a u64", a.1, b.1);
+    let modulus: u64 = (m1 / g)
+        .checked_mul(m2)
+        .and_then(|m| m.try_into().ok())
+        .ok_or_else(overflow)?;
+    let r = (r1 + m1.checked_mul(k).ok_or_else(overflow)?).rem_euclid(modulus as i128);
+    Ok(Some((r as u64, modulus)))
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    // 0, 1, 2, 3, 4, 5, 6, 3, 4, ...
+    fn rho(&x: &u32) -> u32 {
+        if x < 3 {
+            x + 1
+        } else {
+            3 + (x - 2) % 4
+        }
+    }
+
+    #[test]
+    fn test_rho() {
+        let expected = Cycle {
+            prefix: 3,
+            period: 4,
+        };
+        assert_eq!(brent(&0, rho), expected);
+        assert_eq!(floyd(&0, rho), expected);
+
+        let history = History::find(0, rho);
+        assert_eq!(history.cycle, expected);
+        assert_eq!(history.states, vec![0, 1, 2, 3, 4, 5, 6]);
+        assert_eq!(*history.state_at(2), 2);
+        assert_eq!(*history.state_at(7), 3);
+        assert_eq!(*history.state_at(1_000_000_000), 4);
+        assert_eq!(state_after(0, rho, 1_000_000_000), 4);
+    }
+
+    #[test]
+    fn test_pure_cycle() {
+        let step = |&x: &u8| (x + 1) % 5;
+        let expected = Cycle {
+            prefix: 0,
+            period: 5,
+        };
+        assert_eq!(brent(&2, step), expected);
+        assert_eq!(floyd(&2, step), expected);
+        assert_eq!(History::find(2, step).cycle, expected);
+        assert_eq!(state_after(2, step, 11), 3);
+    }
+
+    #[test]
+    fn test_methods_agree() {
+        for seed in 0..50u64 {
+            let step = |&x: &u64| (x * x + seed) % 1009;
+            let cycle = History::find(seed, step).cycle;
+            assert_eq!(brent(&seed, step), cycle);
+            assert_eq!(floyd(&seed, step), cycle);
+
+            let direct = (0..1000).fold(seed, |s, _| step(&s));
+            assert_eq!(state_after(seed, step, 1000), direct);
+        }
+    }
+
+    #[test]
+    fn test_crt() {
+        assert_eq!(crt((2, 3), (3, 5)).unwrap(), Some((8, 15)));
+        assert_eq!(crt((0, 1), (4, 7)).unwrap(), Some((4, 7)));
+        // moduli with a common factor
+        assert_eq!(crt((3, 6), (7, 10)).unwrap(), Some((27, 30)));
+        assert_eq!(crt((1, 4), (2, 6)).unwrap(), None);
+        // no common residue is an answer, overflowing is a failure
+        assert!(crt((u64::MAX - 1, u64::MAX), (0, 2)).is_err());
+        // coprime, with a product near 2^128
+        assert!(crt((1, u64::MAX), (2, u64::MAX - 1)).is_err());
+        assert_eq!(crt((u64::MAX - 1, u64::MAX), (7, u64::MAX)).unwrap(), None);
+        assert_eq!(
+            crt((7, u64::MAX), (7, u64::MAX)).unwrap(),
+            Some((7, u64::MAX))
+        );
+        for (a, b) in [((5, 12), (11, 18)), ((17, 100), (3, 7)), ((0, 9), (6, 15))] {
+            let (r, m) = crt(a, b).unwrap().unwrap();
+            assert_eq!((r % a.1, r % b.1), (a.0, b.0));
+            assert_eq!(m, num::integer::lcm(a.1, b.1));
+        }
+    }
+}
@@ -1,238 +1,496 @@
 use crate::{read_file, PuzzleRun};
 use lazy_static::lazy_static;
+use std::{
+    cmp::{max_by_key, min_by_key, Reverse},
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::Path,
+};
 
 pub(crate) fn get_runs() -> Vec<Box<dyn PuzzleRun>> {
-    vec![Box::new(Part2)]
+    vec![Box::new(Part1), Box::new(Part2)]
 }
 
-#[allow(dead_code)]
-struct Part1;
+/**
+ * Words to look for in a line and the digit each one stands for
+ */
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Vocabulary {
+    words: Vec<(String, u32)>,
+}
 
-impl Part1 {
-    fn _extract(line: &str) -> anyhow::Result<u32> {
-        Ok(10
-            * line
-                .chars()
-                .find(|c| c.is_ascii_digit())
-                .unwrap()
-                .to_digit(10)
-                .unwrap()
-            + line
-                .chars()
-                .rev()
-                .find(|c| c.is_ascii_digit())
-                .unwrap()
-                .to_digit(10)
-                .unwrap())
+impl Vocabulary {
+    pub(crate) fn from_words<S: Into<String>, I: IntoIterator<Item = (S, u32)>>(words: I) -> Self {
+        Self {
+            words: words.into_iter().map(|(w, v)| (w.into(), v)).collect(),
+        }
+    }
+
+    pub(crate) fn with(mut self, word: &str, value: u32) -> Self {
+        self.words.push((word.to_string(), value));
+        self
+    }
+
+    /**
+     * Just the digits 0 to 9
+     */
+    pub(crate) fn digits() -> Self {
+        Self::from_words((0..10).map(|d| (d.to_string(), d)))
+    }
+
+    fn digits_and(names: [&str; 10]) -> Self {
+        names
+            .into_iter()
+            .zip(0..)
+            .fold(Self::digits(), |v, (w, d)| v.with(w, d))
+    }
+
+    pub(crate) fn english() -> Self {
+        Self::digits_and([
+            "zero", "one", "two", "three", "four", "five", "six", "seven", "eight", "nine",
+        ])
+    }
+
+    pub(crate) fn german() -> Self {
+        Self::digits_and([
+            "null", "eins", "zwei", "drei", "vier", "fünf", "sechs", "sieben", "acht", "neun",
+        ])
+    }
+
+    pub(crate) fn french() -> Self {
+        Self::digits_and([
+            "zéro", "un", "deux", "trois", "quatre", "cinq", "six", "sept", "huit", "neuf",
+        ])
     }
 }
 
-struct Part2;
+/**
+ * One word found in a line, by byte offset
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Match {
+    pub(crate) start: usize,
+    pub(crate) len: usize,
+    pub(crate) value: u32,
+}
 
-enum CheckResult {
-    Nan,
-    Number(u8),
-    Candidate,
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Calibration {
+    pub(crate) first: Match,
+    pub(crate) last: Match,
 }
 
-lazy_static! {
-    static ref DIGITS: [Vec<u8>; 10] = [
-        b"zero".to_vec(),
-        b"one".to_vec(),
-        b"two".to_vec(),
-        b"three".to_vec(),
-        b"four".to_vec(),
-        b"five".to_vec(),
-        b"six".to_vec(),
-        b"seven".to_vec(),
-        b"eight".to_vec(),
-        b"nine".to_vec(),
-    ];
+impl Calibration {
+    pub(crate) fn value(&self) -> u32 {
+        self.first.value * 10 + self.last.value
+    }
 }
 
-impl Part2 {
-    fn check_forward(buf: &[u8]) -> CheckResult {
-        for i in 0..DIGITS.len() {
-            if buf == DIGITS[i] {
-                return CheckResult::Number(i as u8);
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub(crate) enum Language {
+    Digits,
+    English,
+    German,
+    French,
+}
+
+impl From<Language> for Vocabulary {
+    fn from(language: Language) -> Self {
+        match language {
+            Language::Digits => Self::digits(),
+            Language::English => Self::english(),
+            Language::German => Self::german(),
+            Language::French => Self::french(),
+        }
+    }
+}
+
+const ROOT: usize = 0;
+
+/**
+ * Aho-Corasick automaton over the bytes of a vocabulary's words, so a line is
+ * read once whatever the number of words, and words that overlap ("twone")
+ * are all seen.
+ */
+pub(crate) struct Scanner {
+    // full transition table: no need to follow failure links while scanning
+    next: Vec<[usize; 256]>,
+    // (length, value) of every word ending at each state
+    outputs: Vec<Vec<(usize, u32)>>,
+}
+
+impl Scanner {
+    pub(crate) fn new(vocabulary: &Vocabulary) -> Self {
+        const NONE: usize = usize::MAX;
+        let mut next = vec![[NONE; 256]];
+        let mut outputs: Vec<Vec<(usize, u32)>> = vec![vec![]];
+
+        for (word, value) in &vocabulary.words {
+            let mut state = ROOT;
+            for &b in word.as_bytes() {
+                if next[state][b as usize] == NONE {
+                    next[state][b as usize] = next.len();
+                    next.push([NONE; 256]);
+                    outputs.push(vec![]);
+                }
+                state = next[state][b as usize];
             }
-            if buf.len() < DIGITS[i].len() && buf[..] == DIGITS[i].as_slice()[0..buf.len()] {
-                return CheckResult::Candidate;
+            outputs[state].push((word.len(), *value));
+        }
+
+        // breadth first, so every state's failure target is finished first
+        let mut fail = vec![ROOT; next.len()];
+        let mut queue = std::collections::VecDeque::new();
+        for b in 0..256 {
+            match next[ROOT][b] {
+                NONE => next[ROOT][b] = ROOT,
+                child => queue.push_back(child),
+            }
+        }
+        while let Some(state) = queue.pop_front() {
+            for b in 0..256 {
+                let fallback = next[fail[state]][b];
+                match next[state][b] {
+                    NONE => next[state][b] = fallback,
+                    child => {
+                        fail[child] = fallback;
+                        let inherited = outputs[fallback].clone();
+                        outputs[child].extend(inherited);
+                        queue.push_back(child);
+                    }
+                }
             }
         }
-        CheckResult::Nan
+        Self { next, outputs }
     }
 
-    fn check_backward(buf: &[u8]) -> CheckResult {
-        for i in 0..DIGITS.len() {
-            if buf.len() >= DIGITS[i].len() && buf[0..DIGITS[i].len()] == DIGITS[i] {
-                return CheckResult::Number(i as u8);
-            }
+    /**
+     * Every match in the line, in order of where they end
+     */
+    pub(crate) fn matches<'a>(&'a self, line: &'a [u8]) -> impl Iterator<Item = Match> + 'a {
+        line.iter()
+            .copied()
+            .enumerate()
+            .scan(ROOT, |state, (i, b)| {
+                *state = self.next[*state][b as usize];
+                Some((i, *state))
+            })
+            .flat_map(|(i, state)| {
+                self.outputs[state].iter().map(move |&(len, value)| Match {
+                    start: i + 1 - len,
+                    len,
+                    value,
+                })
+            })
+    }
+
+    /**
+     * The matches that start first and last in the line. Where two start at
+     * the same place the longer one wins.
+     */
+    pub(crate) fn scan<L: AsRef<[u8]>>(&self, line: L) -> anyhow::Result<Calibration> {
+        let line = line.as_ref();
+        let mut found: Option<Calibration> = None;
+        for m in self.matches(line) {
+            found = Some(match found {
+                None => Calibration { first: m, last: m },
+                Some(Calibration { first, last }) => Calibration {
+                    first: min_by_key(first, m, |m| (m.start, Reverse(m.len))),
+                    last: max_by_key(last, m, |m| (m.start, m.len)),
+                },
+            });
         }
-        CheckResult::Nan
+        found.ok_or_else(|| anyhow::anyhow!("no digit in {:?}", String::from_utf8_lossy(line)))
     }
+}
 
-    fn extract(line: &str) -> u32 {
-        assert!(line.is_ascii());
-        let bytes = line.as_bytes();
+/**
+ * One line's result from a `Stream`, borrowing the line until the next one
+ * is read
+ */
+pub(crate) struct LineResult<'a> {
+    pub(crate) line_no: usize,
+    pub(crate) line: &'a [u8],
+    pub(crate) calibration: anyhow::Result<Calibration>,
+    pub(crate) total: u64,
+}
 
-        let fw = {
-            let mut buf: Vec<u8> = Vec::new();
-            let mut i = 0;
-            let mut val: Option<u8> = None;
-            while i < bytes.len() {
-                if bytes[i].is_ascii_digit() {
-                    val = Some(bytes[i] - 48);
-                    break;
-                }
-                buf.push(bytes[i]);
-                match Self::check_forward(&buf) {
-                    CheckResult::Number(n) => {
-                        val = Some(n);
-                        break;
-                    }
-                    CheckResult::Nan => {
-                        buf.remove(0);
-                    }
-                    CheckResult::Candidate => {}
-                }
-                i += 1;
+/**
+ * Calibration values read a line at a time from any `BufRead`, reusing one
+ * line buffer so memory stays flat however big the input is
+ */
+pub(crate) struct Stream<'s, R> {
+    scanner: &'s Scanner,
+    reader: R,
+    buf: Vec<u8>,
+    line_no: usize,
+    total: u64,
+}
+
+impl<'s, R: BufRead> Stream<'s, R> {
+    pub(crate) fn new(scanner: &'s Scanner, reader: R) -> Self {
+        Self {
+            scanner,
+            reader,
+            buf: vec![],
+            line_no: 0,
+            total: 0,
+        }
+    }
+
+    pub(crate) fn next_line(&mut self) -> io::Result<Option<LineResult<'_>>> {
+        self.buf.clear();
+        if self.reader.read_until(b'\n', &mut self.buf)? == 0 {
+            return Ok(None);
+        }
+        self.line_no += 1;
+        let line = self.buf.trim_ascii_end();
+        let calibration = self.scanner.scan(line);
+        if let Ok(c) = &calibration {
+            self.total += c.value() as u64;
+        }
+        Ok(Some(LineResult {
+            line_no: self.line_no,
+            line,
+            calibration,
+            total: self.total,
+        }))
+    }
+
+    /**
+     * Sum of every line, stopping at the first line with no digit
+     */
+    pub(crate) fn total(mut self) -> anyhow::Result<u64> {
+        while let Some(result) = self.next_line()? {
+            if let Err(e) = result.calibration {
+                anyhow::bail!("line {}: {}", result.line_no, e);
             }
-            val.unwrap()
-        };
-
-        let bw = {
-            let mut i = bytes.len() - 1;
-            let val: Option<u8>;
-            loop {
-                if bytes[i].is_ascii_digit() {
-                    val = Some(bytes[i] - 48);
-                    break;
-                }
-                match Self::check_backward(&bytes[i..]) {
-                    CheckResult::Number(n) => {
-                        val = Some(n);
-                        break;
-                    }
-                    CheckResult::Nan => {}
-                    CheckResult::Candidate => {}
+        }
+        Ok(self.total)
+    }
+
+    /**
+     * Write each line's value and the running total to `out`, and with
+     * `explain` the words matched and their byte offsets. Lines with no digit
+     * are reported to `err` and skipped.
+     */
+    pub(crate) fn write_to<W: Write, E: Write>(
+        mut self,
+        mut out: W,
+        mut err: E,
+        explain: bool,
+    ) -> anyhow::Result<u64> {
+        while let Some(result) = self.next_line()? {
+            let c = match result.calibration {
+                Ok(c) => c,
+                Err(e) => {
+                    writeln!(err, "line {}: {}", result.line_no, e)?;
+                    continue;
                 }
-                if i == 0 {
-                    panic!();
-                } else {
-                    i -= 1;
+            };
+            write!(out, "{}\t{}\t{}", result.line_no, c.value(), result.total)?;
+            if explain {
+                for m in [c.first, c.last] {
+                    let word = String::from_utf8_lossy(&result.line[m.start..m.start + m.len]);
+                    write!(out, "\t{}={}@{}", word, m.value, m.start)?;
                 }
             }
-            val.unwrap()
-        };
-        println!("{}: {}", line, fw as u32 * 10 + bw as u32);
-        fw as u32 * 10 + bw as u32
+            writeln!(out)?;
+        }
+        out.flush()?;
+        Ok(self.total)
     }
 }
-impl PuzzleRun for Part2 {
+
+/**
+ * Stream calibration values for a file, or stdin if `path` is "-"
+ */
+pub(crate) fn stream_file(path: &Path, language: Language, explain: bool) -> anyhow::Result<()> {
+    let scanner = Scanner::new(&language.into());
+    let out = BufWriter::new(io::stdout().lock());
+    let total = if path == Path::new("-") {
+        Stream::new(&scanner, io::stdin().lock()).write_to(out, io::stderr(), explain)
+    } else {
+        let file = BufReader::new(File::open(path)?);
+        Stream::new(&scanner, file).write_to(out, io::stderr(), explain)
+    };
+    match total {
+        Ok(total) => eprintln!("total: {}", total),
+        // piped into `head` or similar, which has seen enough
+        Err(e) if matches!(e.downcast_ref::<io::Error>(), Some(e) if e.kind() == io::ErrorKind::BrokenPipe) =>
+            {}
+        Err(e) => return Err(e),
+    }
+    Ok(())
+}
+
+lazy_static! {
+    static ref DIGITS: Scanner = Scanner::new(&Vocabulary::digits());
+    static ref ENGLISH: Scanner = Scanner::new(&Vocabulary::english());
+}
+
+struct Part1;
+
+#[cfg(test)]
+impl Part1 {
+    fn extract(line: &str) -> anyhow::Result<u32> {
+        Ok(DIGITS.scan(line)?.value())
+    }
+}
+
+impl PuzzleRun for Part1 {
     fn input_data(&self) -> anyhow::Result<&str> {
         read_file("input/day1.txt")
     }
 
     fn run(&self, input: &str) -> String {
-        let sum: u32 = input.lines().map(Part2::extract).sum();
+        let sum = Stream::new(&DIGITS, input.as_bytes())
+            .total()
+            .expect("every line should have a digit");
         format!("{}", sum)
     }
 }
 
-#[cfg(test)]
-mod test {
-
-    use super::CheckResult;
-    use super::Part2;
+struct Part2;
 
-    #[test]
-    fn test_check_forward() {
-        let buf = b"six";
-        assert!(matches!(
-            Part2::check_forward(buf.as_slice()),
-            CheckResult::Number(6)
-        ));
-
-        let buf = b"si";
-        assert!(matches!(
-            Part2::check_forward(buf.as_slice()),
-            CheckResult::Candidate
-        ));
-
-        let buf = b"q";
-        assert!(matches!(
-            Part2::check_forward(buf.as_slice()),
-            CheckResult::Nan
-        ));
-
-        let buf = b"siq";
-        assert!(matches!(
-            Part2::check_forward(buf.as_slice()),
-            CheckResult::Nan
-        ));
+#[cfg(test)]
+impl Part2 {
+    fn extract(line: &str) -> anyhow::Result<u32> {
+        Ok(ENGLISH.scan(line)?.value())
+    }
+}
+impl PuzzleRun for Part2 {
+    fn input_data(&self) -> anyhow::Result<&str> {
+        read_file("input/day1.txt")
     }
 
-    #[test]
-    fn test_check_backward() {
-        let buf = b"six";
-        assert!(matches!(
-            Part2::check_backward(buf.as_slice()),
-            CheckResult::Number(6)
-        ));
-
-        let buf = b"ix";
-        assert!(matches!(
-            Part2::check_backward(buf.as_slice()),
-            CheckResult::Nan
-        ));
-
-        let buf = b"qix";
-        assert!(matches!(
-            Part2::check_backward(buf.as_slice()),
-            CheckResult::Nan
-        ));
-
-        let buf = b"threexxx";
-        assert!(matches!(
-            Part2::check_backward(buf.as_slice()),
-            CheckResult::Number(3)
-        ));
+    fn run(&self, input: &str) -> String {
+        let sum = Stream::new(&ENGLISH, input.as_bytes())
+            .total()
+            .expect("every line should have a digit or a digit word");
+        format!("{}", sum)
     }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
 
     #[test]
     fn test_extract() {
         let buf = "two1nine";
-        assert_eq!(Part2::extract(buf), 29);
+        assert_eq!(Part2::extract(buf).unwrap(), 29);
 
         let buf = "eightwothree";
-        assert_eq!(Part2::extract(buf), 83);
+        assert_eq!(Part2::extract(buf).unwrap(), 83);
 
         let buf = "abcone2threexyz";
-        assert_eq!(Part2::extract(buf), 13);
+        assert_eq!(Part2::extract(buf).unwrap(), 13);
 
         let buf = "xtwone3four";
-        assert_eq!(Part2::extract(buf), 24);
+        assert_eq!(Part2::extract(buf).unwrap(), 24);
 
         let buf = "4nineeightseven2";
-        assert_eq!(Part2::extract(buf), 42);
+        assert_eq!(Part2::extract(buf).unwrap(), 42);
 
         let buf = "zoneight234";
-        assert_eq!(Part2::extract(buf), 14);
+        assert_eq!(Part2::extract(buf).unwrap(), 14);
 
         let buf = "7pqrstsixteen";
-        assert_eq!(Part2::extract(buf), 76);
+        assert_eq!(Part2::extract(buf).unwrap(), 76);
 
         let buf = "eighthree";
-        assert_eq!(Part2::extract(buf), 83);
+        assert_eq!(Part2::extract(buf).unwrap(), 83);
 
         let buf = "sevenine";
-        assert_eq!(Part2::extract(buf), 79);
+        assert_eq!(Part2::extract(buf).unwrap(), 79);
 
         let buf = "5hgkfhkqvbj";
-        assert_eq!(Part2::extract(buf), 55);
+        assert_eq!(Part2::extract(buf).unwrap(), 55);
 
         let buf = "slqhhldzjfdjzeightlskpbpcd5";
-        assert_eq!(Part2::extract(buf), 85);
+        assert_eq!(Part2::extract(buf).unwrap(), 85);
+    }
+
+    #[test]
+    fn test_no_match() {
+        assert!(Part2::extract("abcdef").is_err());
+        assert!(Part2::extract("").is_err());
+        assert!(Part1::extract("one").is_err());
+        assert_eq!(Part1::extract("a1b2c3").unwrap(), 13);
+    }
+
+    #[test]
+    fn test_overlapping_matches() {
+        let m: Vec<_> = ENGLISH.matches(b"twoneight").collect();
+        assert_eq!(
+            m,
+            vec![
+                Match {
+                    start: 0,
+                    len: 3,
+                    value: 2
+                },
+                Match {
+                    start: 2,
+                    len: 3,
+                    value: 1
+                },
+                Match {
+                    start: 4,
+                    len: 5,
+                    value: 8
+                },
+            ]
+        );
+        let c = ENGLISH.scan("xtwoneq").unwrap();
+        assert_eq!((c.first.start, c.last.start), (1, 3));
+        assert_eq!(c.value(), 21);
+    }
+
+    #[test]
+    fn test_vocabularies() {
+        let german = Scanner::new(&Vocabulary::german());
+        assert_eq!(german.scan("xfünfzehnachtx").unwrap().value(), 58);
+        assert_eq!(german.scan("zweinsx").unwrap().value(), 21);
+
+        let french = Scanner::new(&Vocabulary::french());
+        assert_eq!(french.scan("zérotroisquatre").unwrap().value(), 4);
+
+        // a word inside another word still counts
+        let custom = Scanner::new(&Vocabulary::from_words([("ab", 1), ("abcd", 2), ("bc", 3)]));
+        let c = custom.scan("abcd").unwrap();
+        assert_eq!((c.first.value, c.last.value), (2, 3));
+        assert!(custom.scan("acbd").is_err());
+    }
+
+    #[test]
+    fn test_stream() {
+        let input = b"two1nine\r\nnothing here\nxtwone3four\n\xffeight";
+        let scanner = Scanner::new(&Vocabulary::english());
+
+        let mut stream = Stream::new(&scanner, &input[..]);
+        let first = stream.next_line().unwrap().unwrap();
+        assert_eq!(
+            (first.line_no, first.line, first.total),
+            (1, &b"two1nine"[..], 29)
+        );
+        assert!(stream.next_line().unwrap().unwrap().calibration.is_err());
+        assert_eq!(stream.next_line().unwrap().unwrap().total, 29 + 24);
+
+        let err = Stream::new(&scanner, &input[..]).total().unwrap_err();
+        assert!(err.to_string().starts_with("line 2:"));
+
+        let (mut out, mut err) = (vec![], vec![]);
+        let total = Stream::new(&scanner, &b"two1nine\nnothing\nxtwone3four\n"[..])
+            .write_to(&mut out, &mut err, true)
+            .unwrap();
+        assert_eq!(total, 53);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "1\t29\t29\ttwo=2@0\tnine=9@4\n3\t24\t53\ttwo=2@1\tfour=4@7\n"
+        );
+        assert!(String::from_utf8(err).unwrap().starts_with("line 2: "));
     }
 }
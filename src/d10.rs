@@ -1,5 +1,16 @@
-use crate::{read_file, PuzzleRun};
-use std::ops::{Index, IndexMut};
+use crate::{
+    grid::{self, Dir},
+    read_file,
+    render::{Image, BLACK, YELLOW},
+    search::bfs_distances,
+    PuzzleRun,
+};
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+    ops::{Index, IndexMut},
+    path::Path,
+};
 
 pub(crate) fn get_runs() -> std::vec::Vec<Box<dyn PuzzleRun>> {
     vec![Box::new(Part1), Box::new(Part2)]
@@ -32,7 +43,75 @@ impl From<u8> for Symbol {
         }
     }
 }
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+
+impl Symbol {
+    fn ascii(self) -> char {
+        match self {
+            Symbol::Vert => '|',
+            Symbol::Horz => '-',
+            Symbol::Ell => 'L',
+            Symbol::Jay => 'J',
+            Symbol::Seven => '7',
+            Symbol::Eff => 'F',
+            Symbol::Start => 'S',
+            Symbol::Dot => '.',
+        }
+    }
+
+    fn box_char(self) -> char {
+        match self {
+            Symbol::Vert => '│',
+            Symbol::Horz => '─',
+            Symbol::Ell => '└',
+            Symbol::Jay => '┘',
+            Symbol::Seven => '┐',
+            Symbol::Eff => '┌',
+            Symbol::Start => 'S',
+            Symbol::Dot => '.',
+        }
+    }
+
+    /**
+     * The two directions a pipe leads. Ground has none, and neither does `S`
+     * until the pipe under it is known.
+     */
+    fn exits(self) -> Option<[Dir; 2]> {
+        match self {
+            Symbol::Vert => Some([Dir::N, Dir::S]),
+            Symbol::Horz => Some([Dir::E, Dir::W]),
+            Symbol::Ell => Some([Dir::N, Dir::E]),
+            Symbol::Jay => Some([Dir::N, Dir::W]),
+            Symbol::Seven => Some([Dir::S, Dir::W]),
+            Symbol::Eff => Some([Dir::S, Dir::E]),
+            Symbol::Start | Symbol::Dot => None,
+        }
+    }
+
+    fn from_exits(a: Dir, b: Dir) -> Option<Self> {
+        use Symbol::*;
+        [Vert, Horz, Ell, Jay, Seven, Eff].into_iter().find(|s| {
+            s.exits()
+                .is_some_and(|e| e.contains(&a) && e.contains(&b) && a != b)
+        })
+    }
+
+    /**
+     * Which way to leave this tile after entering it heading `dir`, if the
+     * pipe accepts something coming from that side
+     */
+    fn leave(self, dir: Dir) -> Option<Dir> {
+        let [a, b] = self.exits()?;
+        let from = dir.opposite();
+        if a == from {
+            Some(b)
+        } else if b == from {
+            Some(a)
+        } else {
+            None
+        }
+    }
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 struct Coord {
     row: u16,
     col: u16,
@@ -70,54 +149,6 @@ impl Coord {
             col: self.col + 1,
         }
     }
-    fn next(&self, last: Coord, symbol: Symbol) -> Self {
-        match symbol {
-            Symbol::Vert => {
-                if last.row < self.row {
-                    self.south()
-                } else {
-                    self.north()
-                }
-            }
-            Symbol::Horz => {
-                if last.col < self.col {
-                    self.east()
-                } else {
-                    self.west()
-                }
-            }
-            Symbol::Ell => {
-                if last.row < self.row {
-                    self.east()
-                } else {
-                    self.north()
-                }
-            }
-            Symbol::Jay => {
-                if last.row < self.row {
-                    self.west()
-                } else {
-                    self.north()
-                }
-            }
-            Symbol::Seven => {
-                if last.row == self.row {
-                    self.south()
-                } else {
-                    self.west()
-                }
-            }
-            Symbol::Eff => {
-                if last.row == self.row {
-                    self.south()
-                } else {
-                    self.east()
-                }
-            }
-            Symbol::Start => panic!("can't move from start"),
-            Symbol::Dot => panic!("Can't move from dot"),
-        }
-    }
 }
 
 #[derive(Debug)]
@@ -174,20 +205,167 @@ impl Grid {
             None
         }
     }
+
+    fn step(&self, c: Coord, dir: Dir) -> Option<Coord> {
+        match dir {
+            Dir::N => self.north(c),
+            Dir::S => self.south(c),
+            Dir::E => self.east(c),
+            Dir::W => self.west(c),
+        }
+    }
+
+    /**
+     * Marks the tiles on `main` and puts the inferred pipe in place of `S`
+     */
+    fn mark(&mut self, main: &Loop) {
+        for &c in &main.path {
+            self[c].onpath = true;
+        }
+        self[main.path[0]].symbol = main.start_symbol;
+    }
+
+    /**
+     * Which tiles the marked loop encloses, row by row. Pipes can run side by
+     * side with a gap to squeeze between, so each tile is blown up to 3x3
+     * with its pipe drawn through the middle before asking which cells the
+     * loop walls off, and a tile is enclosed if its centre is.
+     */
+    fn enclosed(&self) -> Vec<bool> {
+        let (cols, rows) = (self.cols as usize, self.rows as usize);
+        let walls = grid::Grid::new_from(3 * cols, 3 * rows, |x, y| {
+            let node = &self[Coord::new((y / 3) as u16, (x / 3) as u16)];
+            let leads = |dir| node.symbol.exits().is_some_and(|e| e.contains(&dir));
+            node.onpath
+                && match (x % 3, y % 3) {
+                    (1, 1) => true,
+                    (1, 0) => leads(Dir::N),
+                    (1, 2) => leads(Dir::S),
+                    (0, 1) => leads(Dir::W),
+                    (2, 1) => leads(Dir::E),
+                    _ => false,
+                }
+        });
+        let inside = walls.enclosed_by(|&wall| wall);
+        (0..rows)
+            .flat_map(|row| (0..cols).map(move |col| (row, col)))
+            .map(|(row, col)| *inside.get(3 * col + 1, 3 * row + 1))
+            .collect()
+    }
+
+    fn count_enclosed(&self) -> usize {
+        self.enclosed().into_iter().filter(|&e| e).count()
+    }
+
+    /**
+     * The marked loop in yellow on black, one pixel per tile
+     */
+    fn image(&self) -> Image {
+        Image::from_fn(self.cols as usize, self.rows as usize, |x, y| {
+            if self[Coord::new(y as u16, x as u16)].onpath {
+                YELLOW
+            } else {
+                BLACK
+            }
+        })
+    }
+
+    /**
+     * Draws the marked grid with box-drawing pipes: the main loop
+     * highlighted with `S` picked out, enclosed tiles as `I` and junk pipes
+     * dimmed, or left as plain ASCII when there are no colours to dim them
+     * with
+     */
+    fn write_maze<W: io::Write>(&self, main: &Loop, style: Style, mut out: W) -> io::Result<()> {
+        let enclosed = self.enclosed();
+        let start = main.path[0];
+        for row in 0..self.rows {
+            for col in 0..self.cols {
+                let c = Coord::new(row, col);
+                let node = &self[c];
+                let (colour, glyph) = if c == start {
+                    (START, node.symbol.box_char())
+                } else if node.onpath {
+                    (LOOP, node.symbol.box_char())
+                } else if enclosed[self.cells_index(c)] {
+                    (ENCLOSED, 'I')
+                } else if node.symbol == Symbol::Dot {
+                    ("", '.')
+                } else if style == Style::Ansi {
+                    (JUNK, node.symbol.box_char())
+                } else {
+                    ("", node.symbol.ascii())
+                };
+                match style {
+                    Style::Ansi if !colour.is_empty() => write!(out, "{}{}\x1b[0m", colour, glyph)?,
+                    _ => write!(out, "{}", glyph)?,
+                }
+            }
+            writeln!(out)?;
+        }
+        Ok(())
+    }
+
+    fn cells_index(&self, c: Coord) -> usize {
+        c.row as usize * self.cols as usize + c.col as usize
+    }
+}
+
+/**
+ * Whether to colour a drawing of the maze for a terminal or keep it to plain
+ * text for a file
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Style {
+    Plain,
+    Ansi,
+}
+
+const START: &str = "\x1b[1;31m";
+const LOOP: &str = "\x1b[1;33m";
+const ENCLOSED: &str = "\x1b[32m";
+const JUNK: &str = "\x1b[2m";
+
+/**
+ * Draws the puzzle input's maze, in colour on stdout and/or as plain text to
+ * a file
+ */
+pub(crate) fn draw(ansi: bool, text: Option<&Path>) -> anyhow::Result<()> {
+    let (mut grid, start) = parse(read_file("input/day10.txt")?);
+    let main = Loop::find(&grid, start)?;
+    grid.mark(&main);
+    if let Some(path) = text {
+        let mut out = io::BufWriter::new(std::fs::File::create(path)?);
+        grid.write_maze(&main, Style::Plain, &mut out)?;
+        out.flush()?;
+    }
+    if ansi {
+        grid.write_maze(&main, Style::Ansi, io::stdout().lock())?;
+    }
+    Ok(())
+}
+
+/**
+ * Saves the main loop as an image, in whichever format the extension of
+ * `path` names
+ */
+pub(crate) fn render(path: &Path) -> anyhow::Result<()> {
+    let (mut grid, start) = parse(read_file("input/day10.txt")?);
+    grid.mark(&Loop::find(&grid, start)?);
+    grid.image().scale(3).save(path)
 }
 
 impl Index<Coord> for Grid {
     type Output = Node;
 
     fn index(&self, index: Coord) -> &Self::Output {
-        let i = index.row as usize * self.cols as usize + index.col as usize;
-        &self.cells[i]
+        &self.cells[self.cells_index(index)]
     }
 }
 
 impl IndexMut<Coord> for Grid {
     fn index_mut(&mut self, index: Coord) -> &mut Self::Output {
-        let i = index.row as usize * self.cols as usize + index.col as usize;
+        let i = self.cells_index(index);
         &mut self.cells[i]
     }
 }
@@ -225,110 +403,110 @@ fn parse(input: &str) -> (Grid, Coord) {
     (Grid::new(v, num_rows, num_cols), start.unwrap())
 }
 
-fn walk_path(grid: &mut Grid, start: Coord) -> (u16, u16, Symbol) {
-    #[derive(Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Debug)]
-    enum Dir {
-        North,
-        South,
-        East,
-        West,
-    }
-    use Dir::*;
-
-    let mut maybe_first_steps: Vec<(Coord, Dir)> = vec![];
+/**
+ * The main loop as the tiles along it in order, starting from `S`, and the
+ * pipe that `S` must be hiding to close it
+ */
+#[derive(Debug)]
+struct Loop {
+    path: Vec<Coord>,
+    start_symbol: Symbol,
+}
 
-    if let Some(g) = grid.north(start) {
-        if grid[g].symbol == Symbol::Vert
-            || grid[g].symbol == Symbol::Seven
-            || grid[g].symbol == Symbol::Eff
-        {
-            maybe_first_steps.push((g, Dir::North));
+impl Loop {
+    /**
+     * Tries each way out of `S` in turn, so pipes that only look like they
+     * join `S` lead to a dead end and are passed over
+     */
+    fn find(grid: &Grid, start: Coord) -> anyhow::Result<Self> {
+        for first in [Dir::N, Dir::E, Dir::S, Dir::W] {
+            let Some((path, last)) = Self::follow(grid, start, first) else {
+                continue;
+            };
+            let start_symbol = Symbol::from_exits(first, last.opposite())
+                .ok_or_else(|| anyhow::anyhow!("loop leaves and enters S the same way"))?;
+            return Ok(Self { path, start_symbol });
         }
+        anyhow::bail!("no loop through S at {:?}", start)
     }
-    if let Some(g) = grid.south(start) {
-        if grid[g].symbol == Symbol::Vert
-            || grid[g].symbol == Symbol::Ell
-            || grid[g].symbol == Symbol::Jay
-        {
-            maybe_first_steps.push((g, Dir::South));
+
+    /**
+     * Follows the pipes out of `start` heading `dir` until they come back,
+     * returning the tiles passed and the heading of the step back into
+     * `start`. `None` if they lead off the grid or into something that
+     * doesn't connect.
+     */
+    fn follow(grid: &Grid, start: Coord, mut dir: Dir) -> Option<(Vec<Coord>, Dir)> {
+        let mut path = vec![start];
+        let mut at = grid.step(start, dir)?;
+        while at != start {
+            path.push(at);
+            dir = grid[at].symbol.leave(dir)?;
+            at = grid.step(at, dir)?;
         }
+        Some((path, dir))
     }
-    if let Some(g) = grid.west(start) {
-        if grid[g].symbol == Symbol::Horz
-            || grid[g].symbol == Symbol::Ell
-            || grid[g].symbol == Symbol::Eff
-        {
-            maybe_first_steps.push((g, Dir::West));
-        }
+
+    /**
+     * Steps from `S` to every tile of the loop, going whichever way round is
+     * shorter
+     */
+    fn distances(&self, grid: &Grid) -> HashMap<Coord, usize> {
+        let start = self.path[0];
+        bfs_distances(start, |&c| {
+            let symbol = if c == start {
+                self.start_symbol
+            } else {
+                grid[c].symbol
+            };
+            symbol
+                .exits()
+                .into_iter()
+                .flatten()
+                .filter_map(move |d| grid.step(c, d))
+        })
     }
-    if let Some(g) = grid.east(start) {
-        if grid[g].symbol == Symbol::Horz
-            || grid[g].symbol == Symbol::Jay
-            || grid[g].symbol == Symbol::Seven
-        {
-            maybe_first_steps.push((g, Dir::East));
-        }
+
+    /**
+     * Steps to the tile furthest along the loop from `S`
+     */
+    fn farthest(&self, grid: &Grid) -> usize {
+        self.distances(grid).into_values().max().unwrap_or(0)
     }
-    if maybe_first_steps.len() != 2 {
-        panic!("invalid starting position");
-    }
-
-    maybe_first_steps.sort_by_key(|d| d.1);
-
-    let start_symbol: Symbol = match (maybe_first_steps[0].1, maybe_first_steps[1].1) {
-        (North, South) => Symbol::Vert,
-        (East, West) => Symbol::Horz,
-        (North, East) => Symbol::Ell,
-        (North, West) => Symbol::Jay,
-        (South, East) => Symbol::Eff,
-        (South, West) => Symbol::Seven,
-        _ => panic!(),
-    };
-
-    let (mut last_l, mut last_r) = (start, start);
-    let (mut steps_l, mut steps_r) = (1_u16, 1_u16);
-    let (mut step_l, mut step_r) = (maybe_first_steps[0].0, maybe_first_steps[1].0);
-    grid[start].onpath = true;
-    grid[step_l].onpath = true;
-    grid[step_r].onpath = true;
-
-    let mut move_l = true;
-
-    while step_l != step_r {
-        if move_l {
-            let next = step_l.next(last_l, grid[step_l].symbol);
-            grid[next].onpath = true;
-            last_l = step_l;
-            step_l = next;
-            steps_l += 1;
-            move_l = false;
-        } else {
-            let next = step_r.next(last_r, grid[step_r].symbol);
-            grid[next].onpath = true;
-            last_r = step_r;
-            step_r = next;
-            steps_r += 1;
-            move_l = true;
-        };
+
+    /**
+     * Twice the area enclosed by the loop, by the shoelace formula over the
+     * tile centres
+     */
+    fn double_area(&self) -> i64 {
+        let n = self.path.len();
+        (0..n)
+            .map(|i| {
+                let (a, b) = (self.path[i], self.path[(i + 1) % n]);
+                a.col as i64 * b.row as i64 - b.col as i64 * a.row as i64
+            })
+            .sum::<i64>()
+            .abs()
+    }
+
+    /**
+     * Tiles enclosed by the loop from Pick's theorem: with every tile of the
+     * loop a lattice point on the boundary, `A = i + b / 2 - 1`
+     */
+    fn interior(&self) -> i64 {
+        (self.double_area() - self.path.len() as i64) / 2 + 1
     }
-    (steps_r, steps_l, start_symbol)
 }
 
 impl PuzzleRun for Part1 {
     fn input_data(&self) -> anyhow::Result<&str> {
         read_file("input/day10.txt")
-
-        /*Ok("..F7.
-        .FJ|.
-        SJ.L7
-        |F--J
-        LJ...")*/
     }
 
     fn run(&self, input: &str) -> String {
-        let (mut grid, start) = parse(input);
-        let (steps_r, steps_l, _) = walk_path(&mut grid, start);
-        format!("right: {}, left: {}", steps_r, steps_l)
+        let (grid, start) = parse(input);
+        let main = Loop::find(&grid, start).unwrap();
+        format!("{}", main.farthest(&grid))
     }
 }
 
@@ -337,19 +515,51 @@ struct Part2;
 impl PuzzleRun for Part2 {
     fn input_data(&self) -> anyhow::Result<&str> {
         read_file("input/day10.txt")
-        /*
-        Ok("...........
-        .S-------7.
-        .|F-----7|.
-        .||.....||.
-        .||.....||.
-        .|L-7.F-J|.
-        .|..|.|..|.
-        .L--J.L--J.
-        ...........")
-        */
-        /*
-            Ok(".F----7F7F7F7F-7....
+    }
+
+    fn run(&self, input: &str) -> String {
+        let (mut grid, start) = parse(input);
+        let main = Loop::find(&grid, start).unwrap();
+        grid.mark(&main);
+
+        let enclosed = grid.count_enclosed();
+        assert_eq!(
+            enclosed as i64,
+            main.interior(),
+            "flood fill and Pick's theorem disagree"
+        );
+        format!("{}", enclosed)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    const SQUARE: &str = "-L|F7
+        7S-7|
+        L|7||
+        -L-J|
+        L|-JF";
+
+    const COMPLEX: &str = "7-F7-
+        .FJ|7
+        SJLL7
+        |F--J
+        LJ.LJ";
+
+    const SQUEEZE: &str = "..........
+        .S------7.
+        .|F----7|.
+        .||....||.
+        .||....||.
+        .|L-7F-J|.
+        .|..||..|.
+        .L--JL--J.
+        ..........";
+
+    const LARGER: &str = ".F----7F7F7F7F-7....
         .|F--7||||||||FJ....
         .||.FJ||||||||L7....
         FJL7L7LJLJ||LJ.L-7..
@@ -358,10 +568,9 @@ impl PuzzleRun for Part2 {
         ....L7.F7||L7|.L7L7|
         .....|FJLJ|FJ|F7|.LJ
         ....FJL-7.||.||||...
-        ....L---J.LJ.LJLJ...")
-        */
-        /*
-            Ok("FF7FSF7F7F7F7F7F---7
+        ....L---J.LJ.LJLJ...";
+
+    const JUNK: &str = "FF7FSF7F7F7F7F7F---7
         L|LJ||||||||||||F--J
         FL-7LJLJ||||||LJL-77
         F--JF--7||LJLJ7F7FJ-
@@ -370,87 +579,121 @@ impl PuzzleRun for Part2 {
         |FFJF7L7F-JF7|JL---7
         7-L-JL7||F7|L7F-7F7|
         L.L7LFJ|||||FJL7||LJ
-        L7JLJL-JLJLJL--JLJ.L")
-            */
-    }
-
-    fn run(&self, input: &str) -> String {
-        #[derive(Clone, Copy, Debug)]
-        enum InOut {
-            Outside,
-            OnFromNorth(bool), // true if we came from inside
-            OnFromSouth(bool),
-            Inside,
-        }
-        use InOut::*;
-        use Symbol::*;
+        L7JLJL-JLJLJL--JLJ.L";
 
+    fn interior_both_ways(input: &str) -> (usize, i64) {
         let (mut grid, start) = parse(input);
-        let mut inside_count = 0_u32;
-
-        let (_, _, start_symbol) = walk_path(&mut grid, start);
-        grid[start].symbol = start_symbol;
-
-        for row in 0..grid.rows {
-            let row_start = Coord::new(row, 0);
-            let mut current_state = if grid[row_start].onpath {
-                match grid[row_start].symbol {
-                    Ell => OnFromNorth(false),
-                    Eff => OnFromSouth(false),
-                    Vert => Inside,
-                    _ => panic!("can't start a row on symbol {:?}", grid[row_start].symbol),
-                }
-            } else {
-                Outside
-            };
+        let main = Loop::find(&grid, start).unwrap();
+        grid.mark(&main);
+        (grid.count_enclosed(), main.interior())
+    }
 
-            for col in 1..grid.cols - 1 {
-                // first/last columns can't be inside
-                let c = Coord::new(row, col);
-                if !grid[c].onpath {
-                    if matches!(current_state, Inside) {
-                        inside_count += 1;
-                    }
-                    continue;
-                }
+    #[test]
+    fn test_part1() {
+        println!("{}", Part1.run(Part1.input_data().unwrap()));
+    }
 
-                // if we're here, the current token is on the path
-                current_state = match (current_state, grid[c].symbol) {
-                    (Outside, Symbol::Vert) => Inside,
-                    (Inside, Symbol::Vert) => Outside,
-                    (Outside, Symbol::Eff) => OnFromSouth(false),
-                    (Inside, Symbol::Eff) => OnFromSouth(true),
-                    (Outside, Symbol::Ell) => OnFromNorth(false),
-                    (Inside, Symbol::Ell) => OnFromNorth(true),
-                    (OnFromSouth(true), Symbol::Seven) => Inside,
-                    (OnFromSouth(false), Symbol::Seven) => Outside,
-                    (OnFromSouth(true), Symbol::Jay) => Outside,
-                    (OnFromSouth(false), Symbol::Jay) => Inside,
-                    (OnFromNorth(true), Symbol::Jay) => Inside,
-                    (OnFromNorth(false), Symbol::Jay) => Outside,
-                    (OnFromNorth(true), Symbol::Seven) => Outside,
-                    (OnFromNorth(false), Symbol::Seven) => Inside,
-                    v @ (OnFromNorth(_) | OnFromSouth(_), Symbol::Horz) => v.0,
-                    x => panic!("at coord {:?} failed to match: {:?}", c, x),
-                }
-            }
+    #[test]
+    fn test_part2() {
+        println!("{}", Part2.run(Part2.input_data().unwrap()));
+    }
+
+    #[test]
+    fn test_examples() {
+        assert_eq!(Part1.run(SQUARE), "4");
+        assert_eq!(Part1.run(COMPLEX), "8");
+        for input in [SQUARE, COMPLEX, LARGER, JUNK] {
+            let (grid, start) = parse(input);
+            let main = Loop::find(&grid, start).unwrap();
+            assert_eq!(main.distances(&grid).len(), main.path.len());
+            assert_eq!(main.farthest(&grid), main.path.len() / 2);
         }
-        format!("{}", inside_count)
+        assert_eq!(interior_both_ways(SQUARE), (1, 1));
+        assert_eq!(interior_both_ways(SQUEEZE), (4, 4));
+        assert_eq!(interior_both_ways(LARGER), (8, 8));
+        assert_eq!(interior_both_ways(JUNK), (10, 10));
     }
-}
 
-#[cfg(test)]
-mod test {
+    #[test]
+    fn test_start_symbol() {
+        let start_symbol = |input| {
+            let (grid, start) = parse(input);
+            Loop::find(&grid, start).unwrap().start_symbol
+        };
+        assert_eq!(start_symbol(SQUARE), Symbol::Eff);
+        assert_eq!(start_symbol(COMPLEX), Symbol::Eff);
+        assert_eq!(start_symbol(JUNK), Symbol::Seven);
+
+        // the pipes above and left of S connect to it but go nowhere
+        let dead_ends = ".|...
+        -S-7.
+        .|.|.
+        .L-J.
+        .....";
+        assert_eq!(start_symbol(dead_ends), Symbol::Eff);
+
+        let (grid, start) = parse("-S-\n.|.");
+        assert!(Loop::find(&grid, start).is_err());
+    }
 
-    use super::*;
+    #[test]
+    fn test_loop_on_edge() {
+        let (grid, start) = parse("S7\nLJ");
+        let main = Loop::find(&grid, start).unwrap();
+        assert_eq!(main.path.len(), 4);
+        assert_eq!((main.farthest(&grid), main.double_area()), (2, 2));
+        assert_eq!(interior_both_ways("S7\nLJ"), (0, 0));
+
+        let edge = "F-7F-7
+        |.LJ.|
+        S-7F7|
+        ..||||
+        ..LJLJ";
+        assert_eq!(interior_both_ways(edge), (2, 2));
+    }
 
     #[test]
-    fn test_part1() {
-        println!("{}", Part1.run(Part1.input_data().unwrap()));
+    fn test_draw() {
+        let (mut grid, start) = parse(SQUARE);
+        let main = Loop::find(&grid, start).unwrap();
+        grid.mark(&main);
+
+        let mut plain = vec![];
+        grid.write_maze(&main, Style::Plain, &mut plain).unwrap();
+        assert_eq!(
+            String::from_utf8(plain).unwrap(),
+            "-L|F7\n7┌─┐|\nL│I│|\n-└─┘|\nL|-JF\n"
+        );
+
+        let mut ansi = vec![];
+        grid.write_maze(&main, Style::Ansi, &mut ansi).unwrap();
+        let ansi = String::from_utf8(ansi).unwrap();
+        let rows: Vec<&str> = ansi.lines().collect();
+        assert!(rows[0].starts_with("\x1b[2m─\x1b[0m\x1b[2m└"));
+        assert!(rows[1].contains("\x1b[1;31m┌\x1b[0m\x1b[1;33m─"));
+        assert!(rows[2].contains("\x1b[32mI\x1b[0m"));
     }
 
     #[test]
-    fn test_part2() {
-        println!("{}", Part2.run(Part2.input_data().unwrap()));
+    fn test_render() {
+        let (mut grid, start) = parse(SQUARE);
+        grid.mark(&Loop::find(&grid, start).unwrap());
+        let mut ppm = vec![];
+        grid.image().write_ppm(&mut ppm).unwrap();
+        let header = b"P6\n5 5\n255\n";
+        assert_eq!(&ppm[..header.len()], header);
+        let pixels: Vec<&[u8]> = ppm[header.len()..].chunks(3).collect();
+        assert_eq!(pixels.len(), 25);
+        for (i, pixel) in pixels.into_iter().enumerate() {
+            let (x, y) = (i % 5, i / 5);
+            let on_loop = (1..=3).contains(&x) && (1..=3).contains(&y) && (x, y) != (2, 2);
+            assert_eq!(
+                pixel,
+                if on_loop { YELLOW } else { BLACK },
+                "({}, {})",
+                x,
+                y
+            );
+        }
     }
 }
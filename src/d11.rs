@@ -1,5 +1,9 @@
-use crate::{read_file, PuzzleRun};
-use std::collections::{BTreeSet, HashSet};
+use crate::{
+    grid::{GridLike, SparseGrid},
+    read_file, PuzzleRun,
+};
+use itertools::Itertools;
+use std::collections::HashSet;
 
 pub(crate) fn get_runs() -> std::vec::Vec<Box<dyn PuzzleRun>> {
     vec![Box::new(Part1), Box::new(Part2)]
@@ -23,12 +27,34 @@ impl Coord {
     }
 }
 
-fn dump(rows: u32, cols: u32, map: &BTreeSet<Coord>) {
+/**
+ * Every galaxy marked on a sparse grid, since after expansion the image is
+ * mostly empty space
+ */
+fn galaxy_map(galaxies: &[Coord]) -> SparseGrid<bool> {
+    let mut map = SparseGrid::new(false);
+    for g in galaxies {
+        map.set(g.col as i64, g.row as i64, true);
+    }
+    map
+}
+
+/**
+ * The galaxies on `map` in row order
+ */
+fn in_order(map: &SparseGrid<bool>) -> Vec<Coord> {
+    map.iter()
+        .map(|((col, row), _)| Coord::new(row as u32, col as u32))
+        .sorted()
+        .collect()
+}
+
+fn dump<G: GridLike<bool>>(map: &G) {
     #![allow(clippy::println_empty_string)]
     let mut id = 0_u32;
-    for r in 0..rows {
-        for c in 0..cols {
-            if map.contains(&Coord::new(r, c)) {
+    for r in 0..map.height() {
+        for c in 0..map.width() {
+            if *map.get(c, r) {
                 id += 1;
                 print!("{}", id);
             } else {
@@ -116,10 +142,11 @@ impl PuzzleRun for Part1 {
         }
 
         let mut total_dist = 0_u32;
-        let galaxy_map: BTreeSet<Coord> = galaxies.into_iter().collect();
-        dump(row, num_cols, &galaxy_map);
-        for galaxy_i in galaxy_map.iter() {
-            for galaxy_j in galaxy_map.range(galaxy_i..) {
+        let mut galaxy_map = galaxy_map(&galaxies);
+        dump(&galaxy_map.view((0, 0), (num_cols as i64 - 1, row as i64 - 1)));
+        let galaxies = in_order(&galaxy_map);
+        for (i, galaxy_i) in galaxies.iter().enumerate() {
+            for galaxy_j in &galaxies[i..] {
                 let dist = galaxy_i.dist(galaxy_j);
                 total_dist += dist;
                 println!("dist from {:?} to {:?}: {}", galaxy_i, galaxy_j, dist)
@@ -206,12 +233,9 @@ impl PuzzleRun for Part2 {
         }
 
         let mut total_dist = 0_u64;
-        let galaxy_map: BTreeSet<Coord> = galaxies.into_iter().collect();
-        for galaxy_i in galaxy_map.iter() {
-            for galaxy_j in galaxy_map.range((
-                std::ops::Bound::Excluded(galaxy_i),
-                std::ops::Bound::Unbounded,
-            )) {
+        let galaxies = in_order(&galaxy_map(&galaxies));
+        for (i, galaxy_i) in galaxies.iter().enumerate() {
+            for galaxy_j in &galaxies[i + 1..] {
                 let dist = galaxy_i.dist(galaxy_j);
                 total_dist += dist as u64;
                 println!("dist from {:?} to {:?}: {}", galaxy_i, galaxy_j, dist)
@@ -234,4 +258,24 @@ mod test {
     fn test_part2() {
         println!("{}", Part2.run(Part2.input_data().unwrap()));
     }
+
+    #[test]
+    fn test_example() {
+        let example = "...#......
+        .......#..
+        #.........
+        ..........
+        ......#...
+        .#........
+        .........#
+        ..........
+        .......#..
+        #...#.....";
+        assert_eq!(Part1.run(example), "374");
+        assert_eq!(Part2.run(example), "82000210");
+
+        let map = galaxy_map(&[Coord::new(2, 0), Coord::new(0, 3), Coord::new(2, 0)]);
+        assert_eq!(map.len(), 2);
+        assert_eq!(in_order(&map), vec![Coord::new(0, 3), Coord::new(2, 0)]);
+    }
 }
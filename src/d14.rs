@@ -1,11 +1,11 @@
-use std::{
-    collections::{hash_map::Entry, HashMap},
-    fmt::Display,
-};
+use std::fmt::Display;
 
 use rayon::iter::{IndexedParallelIterator, IntoParallelRefIterator, ParallelIterator};
 
 use super::{read_file, PuzzleRun};
+use crate::cycle::History;
+use crate::record::Recorder;
+use crate::render::{Image, BLACK, GREY, YELLOW};
 
 pub(crate) fn get_runs() -> std::vec::Vec<Box<dyn PuzzleRun>> {
     vec![Box::new(Part1)]
@@ -176,6 +176,16 @@ impl Grid {
             .sum()
     }
 
+    fn image(&self) -> Image {
+        Image::from_fn(self.width, self.height, |col, row| {
+            match self.get(row, col) {
+                State::Empty => BLACK,
+                State::Round => YELLOW,
+                State::Cube => GREY,
+            }
+        })
+    }
+
     fn index_iterator(&self, d: Dir) -> GridIndexIterator {
         match d {
             Dir::N => GridIndexIterator {
@@ -323,6 +333,20 @@ impl IndexMut<usize> for Cell {
 }
 */
 
+fn spin_cycle(grid: &Grid) -> Grid {
+    let mut grid = grid.clone();
+    for dir in [Dir::N, Dir::W, Dir::S, Dir::E] {
+        for (row, col) in grid.index_iterator(dir) {
+            if let State::Round = grid.get(row, col) {
+                if let Some((to_r, to_c)) = grid.can_move_from(row, col, dir) {
+                    grid.swap(col, row, to_c, to_r);
+                }
+            }
+        }
+    }
+    grid
+}
+
 struct Part1;
 
 #[derive(Debug, Clone, Copy)]
@@ -353,13 +377,22 @@ impl PuzzleRun for Part1 {
     fn run(&self, input: &str) -> String {
         let mut grid = Grid::new(input);
         let dir = Dir::N;
+        let mut recorder = Recorder::new("day14");
 
+        recorder.frame(|| grid.image().scale(4));
         for (row, col) in grid.index_iterator(dir) {
             if let State::Round = grid.get(row, col) {
                 if let Some((to_r, to_c)) = grid.can_move_from(row, col, dir) {
                     grid.swap(col, row, to_c, to_r);
                 }
             }
+            // tilting north sweeps row by row, so show each row once it's done
+            if col == grid.width - 1 {
+                recorder.frame(|| grid.image().scale(4));
+            }
+        }
+        if let Err(e) = recorder.finish() {
+            eprintln!("couldn't save the recording: {:#}", e);
         }
         format!("{}", grid.score(dir))
     }
@@ -385,55 +418,20 @@ impl PuzzleRun for Part2 {
     }
 
     fn run(&self, input: &str) -> String {
-        let mut grid = Grid::new(input);
-        let original_grid = grid.clone();
-        let dirs = [Dir::N, Dir::W, Dir::S, Dir::E];
-        let mut cache: HashMap<Grid, usize> = Default::default();
-        let mut count: usize = 1;
-
-        let (first, second) = loop {
-            for dir in dirs {
-                for (row, col) in grid.index_iterator(dir) {
-                    if let State::Round = grid.get(row, col) {
-                        if let Some((to_r, to_c)) = grid.can_move_from(row, col, dir) {
-                            grid.swap(col, row, to_c, to_r);
-                        }
-                    }
-                }
-            }
-            println!("after {} cycle score is {}", count, grid.score(Dir::N));
-            match cache.entry(grid.clone()) {
-                Entry::Occupied(e) => break (e.get().clone(), count),
-                Entry::Vacant(e) => {
-                    e.insert(count);
-                }
-            }
-            count += 1;
-        };
-
-        println!(
+        let mut recorder = Recorder::new("day14-spin");
+        let history = History::find(Grid::new(input), |grid| {
+            let next = spin_cycle(grid);
+            recorder.frame(|| next.image().scale(4));
+            next
+        });
+        if let Err(e) = recorder.finish() {
+            eprintln!("couldn't save the recording: {:#}", e);
+        }
+        eprintln!(
             "found cycle of length {} starting at {}",
-            second - first,
-            first
+            history.cycle.period, history.cycle.prefix
         );
-        let cycle_len = second - first;
-        let d = first - 1 + ((1_000_000_000 - first + 1) % cycle_len);
-
-        println!("final run of length {}", d);
-        let mut grid = original_grid;
-        for i in 0..d {
-            for dir in dirs {
-                for (row, col) in grid.index_iterator(dir) {
-                    if let State::Round = grid.get(row, col) {
-                        if let Some((to_r, to_c)) = grid.can_move_from(row, col, dir) {
-                            grid.swap(col, row, to_c, to_r);
-                        }
-                    }
-                }
-            }
-            println!("after {} cycle, score is {}", i + 1, grid.score(Dir::N));
-        }
-        format!("{}", grid.score(Dir::N))
+        format!("{}", history.state_at(1_000_000_000).score(Dir::N))
     }
 }
 #[cfg(test)]
@@ -516,4 +514,26 @@ mod test {
     fn test_part2() {
         println!("{}", Part2::run(&Part2, Part2::input_data(&Part2).unwrap()));
     }
+
+    #[test]
+    fn test_spin_cycle_example() {
+        #[rustfmt::skip]
+        let grid = Grid::new(
+       "O....#....
+        O.OO#....#
+        .....##...
+        OO.#O....O
+        .O.....O#.
+        O.#..O.#.#
+        ..O..#O..O
+        .......O..
+        #....###..
+        #OO..#....",
+        );
+
+        let history = History::find(grid, spin_cycle);
+        assert_eq!(history.cycle.prefix, 3);
+        assert_eq!(history.cycle.period, 7);
+        assert_eq!(history.state_at(1_000_000_000).score(Dir::N), 64);
+    }
 }
@@ -6,6 +6,8 @@ use std::{
 
 use super::{read_file, PuzzleRun};
 use crate::grid::{Dir, Grid};
+use crate::record::Recorder;
+use crate::render::{Image, Rgb, BLACK, RED, WHITE, YELLOW};
 
 pub(crate) fn get_runs() -> std::vec::Vec<Box<dyn PuzzleRun>> {
     vec![Box::new(Part2)]
@@ -159,13 +161,43 @@ impl<'a> Display for DebugGrid<'a, Cell> {
 }
 */
 
-fn score(grid: &Grid<Cell>, start: RayState) -> usize {
+fn energised_count(grid: &Grid<Cell>) -> usize {
+    grid.count_cells(|c| !c.visited.is_empty())
+}
+
+fn colour(cell: &Cell) -> Rgb {
+    match (cell.visited.is_empty(), cell.symbol) {
+        (true, Symbol::None) => BLACK,
+        (true, _) => WHITE,
+        (false, Symbol::None) => YELLOW,
+        (false, _) => RED,
+    }
+}
+
+/**
+ * Saves the grid energised from the top left corner as an image, in
+ * whichever format the extension of `path` names
+ */
+pub(crate) fn render(path: &std::path::Path) -> anyhow::Result<()> {
+    let grid: Grid<Cell> =
+        Grid::from_str(read_file("input/day16.txt")?).map_err(anyhow::Error::msg)?;
+    let energised = energise(&grid, RayState::start(), &mut Recorder::off());
+    Image::new(&energised, colour).scale(4).save(path)
+}
+
+/**
+ * Follow the beam from `start`, returning a copy of the grid with every cell
+ * it passed through marked as visited. Records a frame each time a beam
+ * splits off.
+ */
+fn energise(grid: &Grid<Cell>, start: RayState, recorder: &mut Recorder) -> Grid<Cell> {
     let mut grid = grid.clone();
     let mut stack: Vec<RayState> = vec![];
 
     stack.push(start);
 
     'stack: while let Some(mut current) = stack.pop() {
+        recorder.frame(|| Image::new(&grid, colour).scale(4));
         loop {
             ///println!("current: {:?}", current);
             //println!("{}", DebugGrid(&grid));
@@ -192,19 +224,32 @@ fn score(grid: &Grid<Cell>, start: RayState) -> usize {
                     Dir::S
                 }
             };
-            let next = grid.try_next_coord(current.x, current.y, next_dir);
-            if let Some((next_x, next_y)) = next {
-                if grid.get(next_x, next_y).was_visited_from(next_dir) {
-                    continue 'stack;
+            // empty cells pass the beam straight on, so light them all up to
+            // the next mirror or splitter, the edge, or a stretch the beam
+            // has already lit going this way
+            let Some(ray) = grid.ray(current.x, current.y, next_dir) else {
+                continue 'stack;
+            };
+            let mut empty = vec![];
+            let mut next = None;
+            for (x, y, cell) in ray.until(|c| c.was_visited_from(next_dir)) {
+                if let Symbol::None = cell.symbol {
+                    empty.push((x, y));
                 } else {
-                    current = RayState::new(next_x, next_y, next_dir);
+                    next = Some((x, y));
+                    break;
                 }
-            } else {
-                continue 'stack;
+            }
+            for (x, y) in empty {
+                grid.get_mut(x, y).visit(next_dir);
+            }
+            match next {
+                Some((x, y)) => current = RayState::new(x, y, next_dir),
+                None => continue 'stack,
             }
         }
     }
-    grid.count_cells(|c| !c.visited.is_empty())
+    grid
 }
 
 impl FromStr for Grid<Cell> {
@@ -243,8 +288,14 @@ impl PuzzleRun for Part1 {
     }
 
     fn run(&self, input: &str) -> String {
-        let mut grid: Grid<Cell> = Grid::from_str(input).unwrap();
-        format!("{}", score(&grid, RayState::start()))
+        let grid: Grid<Cell> = Grid::from_str(input).unwrap();
+        let mut recorder = Recorder::new("day16");
+        let energised = energise(&grid, RayState::start(), &mut recorder);
+        recorder.frame(|| Image::new(&energised, colour).scale(4));
+        if let Err(e) = recorder.finish() {
+            eprintln!("couldn't save the recording: {:#}", e);
+        }
+        format!("{}", energised_count(&energised))
     }
 }
 
@@ -268,18 +319,27 @@ impl PuzzleRun for Part2 {
     }
 
     fn run(&self, input: &str) -> String {
-        let mut grid: Grid<Cell> = Grid::from_str(input).unwrap();
+        let grid: Grid<Cell> = Grid::from_str(input).unwrap();
+        let mut recorder = Recorder::new("day16-edges");
         let max = (0..grid.width)
             .cartesian_product([Dir::N, Dir::S])
             .chain((0..grid.height).cartesian_product([Dir::E, Dir::W]))
-            .map(|(i, dir)| match dir {
-                Dir::N => score(&grid.clone(), RayState::new(i, grid.height - 1, Dir::N)),
-                Dir::S => score(&grid.clone(), RayState::new(i, 0, Dir::S)),
-                Dir::E => score(&grid.clone(), RayState::new(0, i, Dir::E)),
-                Dir::W => score(&grid.clone(), RayState::new(grid.width - 1, i, Dir::W)),
+            .map(|(i, dir)| {
+                let start = match dir {
+                    Dir::N => RayState::new(i, grid.height - 1, Dir::N),
+                    Dir::S => RayState::new(i, 0, Dir::S),
+                    Dir::E => RayState::new(0, i, Dir::E),
+                    Dir::W => RayState::new(grid.width - 1, i, Dir::W),
+                };
+                let energised = energise(&grid, start, &mut Recorder::off());
+                recorder.frame(|| Image::new(&energised, colour).scale(4));
+                energised_count(&energised)
             })
             .max()
             .unwrap();
+        if let Err(e) = recorder.finish() {
+            eprintln!("couldn't save the recording: {:#}", e);
+        }
 
         format!("{}", max)
     }
@@ -298,4 +358,36 @@ mod test {
     fn test_Part2() {
         println!("{}", Part2::run(&Part2, Part2::input_data(&Part2).unwrap()));
     }
+
+    #[test]
+    fn test_example() {
+        let example = r".|...\....
+        |.-.\.....
+        .....|-...
+        ........|.
+        ..........
+        .........\
+        ..../.\\..
+        .-.-/..|..
+        .|....-|.\
+        ..//.|....";
+        assert_eq!(Part1.run(example), "46");
+        assert_eq!(Part2.run(example), "51");
+    }
+
+    #[test]
+    fn test_render() {
+        let grid: Grid<Cell> = Grid::from_str("\\.\n..").unwrap();
+        let energised = energise(&grid, RayState::start(), &mut Recorder::off());
+        let mut ppm = vec![];
+        Image::new(&energised, colour).write_ppm(&mut ppm).unwrap();
+        assert_eq!(
+            ppm,
+            [
+                b"P6\n2 2\n255\n".as_slice(),
+                &[RED, BLACK, YELLOW, BLACK].concat()
+            ]
+            .concat()
+        );
+    }
 }
@@ -7,7 +7,7 @@ pub(crate) fn get_runs() -> std::vec::Vec<Box<dyn PuzzleRun>> {
     vec![Box::new(Part1)]
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 enum Dir {
     U,
     D,
@@ -41,6 +41,50 @@ impl Step {
         Self { dir, count, code }
     }
 }
+
+fn parse_steps(input: &str) -> Vec<Step> {
+    let re = Regex::new(r"(\D) (\d+) \(#(.+)\)").unwrap();
+    input
+        .lines()
+        .map(str::trim)
+        .map(|s| match re.captures(s).map(|c| c.extract()) {
+            Some((_, [dir, count, code])) => Step::new(
+                dir.parse().unwrap(),
+                count.parse::<u32>().unwrap(),
+                code.to_owned(),
+            ),
+            None => panic!("{}", s),
+        })
+        .collect()
+}
+
+fn step_by(dir: Dir, count: i64, (x, y): (i64, i64)) -> (i64, i64) {
+    match dir {
+        Dir::L => (x - count, y),
+        Dir::R => (x + count, y),
+        Dir::U => (x, y - count),
+        Dir::D => (x, y + count),
+    }
+}
+
+/**
+ * Cubic metres dug out, from the corners of the trench alone: the shoelace
+ * area plus the half of the trench that lies outside it, by Pick's theorem.
+ * https://www.reddit.com/r/adventofcode/comments/18lj7wx/comment/kdz5a7v/
+ */
+fn lagoon_area<I: IntoIterator<Item = (Dir, i64)>>(moves: I) -> i64 {
+    let mut area: i64 = 0;
+    let mut perim: i64 = 0;
+    let (mut x_1, mut y_1): (i64, i64) = (0, 0);
+    for (dir, count) in moves {
+        let (x_2, y_2) = step_by(dir, count, (x_1, y_1));
+        area += x_1 * y_2 - x_2 * y_1;
+        perim += (x_2 - x_1).abs() + (y_2 - y_1).abs();
+        (x_1, y_1) = (x_2, y_2);
+    }
+    area.abs() / 2 + perim / 2 + 1
+}
+
 struct Part1;
 
 impl PuzzleRun for Part1 {
@@ -65,40 +109,9 @@ impl PuzzleRun for Part1 {
     }
 
     fn run(&self, input: &str) -> String {
-        let re = Regex::new(r"(\D) (\d+) \(#(.+)\)").unwrap();
-        let steps: Vec<Step> = input
-            .lines()
-            .map(str::trim)
-            .map(|s| match re.captures(s).map(|c| c.extract()) {
-                Some((_, [dir, count, code])) => Step::new(
-                    dir.parse().unwrap(),
-                    count.parse::<u32>().unwrap(),
-                    code.to_owned(),
-                ),
-                None => panic!("{}", s),
-            })
-            .collect();
-        // using Pick's theoreom: https://www.reddit.com/r/adventofcode/comments/18lj7wx/comment/kdz5a7v/?utm_source=share&utm_medium=web3x&utm_name=web3xcss&utm_term=1&utm_content=share_button
-
-        let mut area: i64 = 0;
-        let mut perim: i64 = 0;
-        let (mut x_1, mut y_1): (i64, i64) = (0, 0);
-        for i in 0..steps.len() {
-            println!("processing step: {:?}", steps[i]);
-            let (x_2, y_2): (i64, i64) = match steps[i].dir {
-                Dir::L => (x_1 - steps[i].count as i64, y_1),
-                Dir::R => (x_1 + steps[i].count as i64, y_1),
-                Dir::U => (x_1, y_1 - steps[i].count as i64),
-                Dir::D => (x_1, y_1 + steps[i].count as i64),
-            };
-            area += x_1 * y_2 - x_2 * y_1;
-            println!("added {} to area", x_1 * y_2 - x_2 * y_1);
-            perim += (x_2 - x_1).abs() + (y_2 - y_1).abs();
-            (x_1, y_1) = (x_2, y_2);
-            println!("moved to {:?}", (x_1, y_1));
-        }
-        println!("area: {}", area / 2);
-        format!("{}", (area / 2) + (perim / 2) + 1)
+        let steps = parse_steps(input);
+        let area = lagoon_area(steps.iter().map(|s| (s.dir, s.count as i64)));
+        format!("{}", area)
     }
 }
 struct Part2;
@@ -139,41 +152,9 @@ impl PuzzleRun for Part2 {
     }
 
     fn run(&self, input: &str) -> String {
-        let re = Regex::new(r"(\D) (\d+) \(#(.+)\)").unwrap();
-        let steps: Vec<Step> = input
-            .lines()
-            .map(str::trim)
-            .map(|s| match re.captures(s).map(|c| c.extract()) {
-                Some((_, [dir, count, code])) => Step::new(
-                    dir.parse().unwrap(),
-                    count.parse::<u32>().unwrap(),
-                    code.to_owned(),
-                ),
-                None => panic!("{}", s),
-            })
-            .collect();
-        // using Pick's theoreom: https://www.reddit.com/r/adventofcode/comments/18lj7wx/comment/kdz5a7v/?utm_source=share&utm_medium=web3x&utm_name=web3xcss&utm_term=1&utm_content=share_button
-
-        let mut area: i64 = 0;
-        let mut perim: i64 = 0;
-        let (mut x_1, mut y_1): (i64, i64) = (0, 0);
-        for i in 0..steps.len() {
-            println!("processing step: {:?}", steps[i]);
-            let (dir, count) = Part2::decode(&steps[i].code);
-            let (x_2, y_2): (i64, i64) = match dir {
-                Dir::L => (x_1 - count as i64, y_1),
-                Dir::R => (x_1 + count as i64, y_1),
-                Dir::U => (x_1, y_1 - count as i64),
-                Dir::D => (x_1, y_1 + count as i64),
-            };
-            area += x_1 * y_2 - x_2 * y_1;
-            println!("added {} to area", x_1 * y_2 - x_2 * y_1);
-            perim += (x_2 - x_1).abs() + (y_2 - y_1).abs();
-            (x_1, y_1) = (x_2, y_2);
-            println!("moved to {:?}", (x_1, y_1));
-        }
-        println!("area: {}", area / 2);
-        format!("{}", (area / 2) + (perim / 2) + 1)
+        let steps = parse_steps(input);
+        let area = lagoon_area(steps.iter().map(|s| Part2::decode(&s.code)));
+        format!("{}", area)
     }
 }
 
@@ -181,12 +162,92 @@ impl PuzzleRun for Part2 {
 mod test {
 
     use super::*;
+    use crate::{grid::SparseGrid, search::bfs_distances};
+
+    /**
+     * The trench dug cell by cell from `(0, 0)`, to check `lagoon_area`
+     * against. Plans wander left and up of the start as well, so the grid
+     * has to take negative coordinates.
+     */
+    fn dig<I: IntoIterator<Item = (Dir, i64)>>(moves: I) -> SparseGrid<bool> {
+        let mut trench = SparseGrid::new(false);
+        let mut at = (0, 0);
+        trench.set(0, 0, true);
+        for (dir, count) in moves {
+            for _ in 0..count {
+                at = step_by(dir, 1, at);
+                trench.set(at.0, at.1, true);
+            }
+        }
+        trench
+    }
+
+    /**
+     * Cells inside the trench's bounds that can't be reached from beyond them
+     * without crossing it
+     */
+    fn dug_cells(trench: &SparseGrid<bool>) -> usize {
+        let ((x0, y0), (x1, y1)) = (trench.min(), trench.max());
+        let (x0, y0, x1, y1) = (x0 - 1, y0 - 1, x1 + 1, y1 + 1);
+        let outside = bfs_distances((x0, y0), |&(x, y)| {
+            [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)]
+                .into_iter()
+                .filter(move |&(x, y)| {
+                    (x0..=x1).contains(&x) && (y0..=y1).contains(&y) && !trench.contains(x, y)
+                })
+        });
+        ((x1 - x0 + 1) * (y1 - y0 + 1)) as usize - outside.len()
+    }
 
     #[test]
     fn test_part1() {
         println!("{}", Part1::run(&Part1, Part1::input_data(&Part1).unwrap()));
     }
 
+    const EXAMPLE: &str = "R 6 (#70c710)
+        D 5 (#0dc571)
+        L 2 (#5713f0)
+        D 2 (#d2c081)
+        R 2 (#59c680)
+        D 2 (#411b91)
+        L 5 (#8ceee2)
+        U 2 (#caa173)
+        L 1 (#1b58a2)
+        U 2 (#caa171)
+        R 2 (#7807d2)
+        U 3 (#a77fa3)
+        L 2 (#015232)
+        U 2 (#7a21e3)";
+
+    #[test]
+    fn test_example() {
+        assert_eq!(Part1.run(EXAMPLE), "62");
+        assert_eq!(Part2.run(EXAMPLE), "952408144115");
+    }
+
+    #[test]
+    fn test_dig_negative() {
+        // a loop that goes up and left of the start
+        let moves = [
+            (Dir::L, 3),
+            (Dir::U, 2),
+            (Dir::R, 5),
+            (Dir::D, 2),
+            (Dir::L, 2),
+        ];
+        let trench = dig(moves);
+        assert_eq!((trench.min(), trench.max()), ((-3, -2), (2, 0)));
+        assert_eq!(trench.len(), 14);
+        assert_eq!(dug_cells(&trench), 18);
+        assert_eq!(lagoon_area(moves) as usize, 18);
+
+        let steps = parse_steps(Part1.input_data().unwrap());
+        let moves: Vec<(Dir, i64)> = steps.iter().map(|s| (s.dir, s.count as i64)).collect();
+        let trench = dig(moves.iter().copied());
+        assert!(trench.min().0 < 0 || trench.min().1 < 0);
+        assert_eq!(dug_cells(&trench) as i64, lagoon_area(moves));
+    }
+
     #[test]
     fn test_part2() {
         println!("{}", Part2::run(&Part2, Part2::input_data(&Part2).unwrap()));
@@ -7,12 +7,15 @@ use std::{
 };
 
 use super::{read_file, PuzzleRun};
+#[cfg(feature = "serde")]
+use crate::dump::{write_model, DumpFormat};
 
 pub(crate) fn get_runs() -> std::vec::Vec<Box<dyn PuzzleRun>> {
     vec![Box::new(Part1)]
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 enum Op {
     Gt,
     Lt,
@@ -20,6 +23,7 @@ enum Op {
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 enum Category {
     X,
     M,
@@ -62,6 +66,7 @@ impl FromStr for Op {
 }
 
 #[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 struct RuleTest {
     category: Category,
     op: Op,
@@ -93,6 +98,7 @@ impl RuleTest {
         }
     }
 }
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 struct WorkflowDesc {
     name: String,
     rules: Vec<RuleDesc>,
@@ -117,6 +123,7 @@ impl WorkflowDesc {
     }
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 struct RuleDesc {
     test: Option<RuleTest>,
     target: String,
@@ -396,6 +403,7 @@ impl Machine {
 }
 
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 struct Part {
     x: u16,
     m: u16,
@@ -522,6 +530,19 @@ impl PuzzleRun for Part1 {
         read_file("input/day19.txt")
     }
 
+    #[cfg(feature = "serde")]
+    fn dump_parsed(
+        &self,
+        input: &str,
+        out: &mut dyn std::io::Write,
+        format: DumpFormat,
+    ) -> anyhow::Result<()> {
+        let mut lines = input.lines();
+        let wf_descs = parse_workflows(&mut lines);
+        let items = parse_items(lines);
+        write_model(&(wf_descs, items), out, format)
+    }
+
     fn run(&self, input: &str) -> String {
         let mut lines = input.lines();
         let wf_descs = parse_workflows(&mut lines);
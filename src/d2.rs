@@ -1,83 +1,145 @@
 use crate::{read_file, PuzzleRun};
 use nom::{
-    branch::alt,
     bytes::complete::tag,
-    character::complete::{digit1, space0},
+    character::complete::{alpha1, digit1, space0},
     combinator::{map, map_res},
     multi::separated_list1,
     sequence::{delimited, pair},
     Finish, IResult,
 };
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
 pub(crate) fn get_runs() -> Vec<Box<dyn PuzzleRun>> {
-    vec![Box::new(Part2)]
+    vec![Box::new(Part1), Box::new(Part2)]
 }
 
-struct Part1;
+/**
+ * Print why each game couldn't have come from the part 1 bag
+ */
+pub(crate) fn explain() -> anyhow::Result<()> {
+    let bag = part1_bag();
+    for line in read_file("input/day2.txt")?.lines() {
+        let game: Game = line
+            .parse()
+            .map_err(|e| anyhow::anyhow!("{:?} in {:?}", e, line))?;
+        if let Some(why) = game.impossible(&bag) {
+            println!("game {}: {}", game.id, why);
+        }
+    }
+    Ok(())
+}
 
-#[derive(Debug, PartialEq, Eq, Hash)]
-enum Color {
-    Red,
-    Green,
-    Blue,
+fn part1_bag() -> Cubes {
+    Cubes::new([("red", 12), ("green", 13), ("blue", 14)])
 }
 
-impl std::str::FromStr for Color {
-    type Err = &'static str;
+struct Part1;
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "red" => Ok(Color::Red),
-            "green" => Ok(Color::Green),
-            "blue" => Ok(Color::Blue),
-            _ => Err("invalid color"),
+/**
+ * A count of cubes for each colour, used both for a single draw and for the
+ * contents of a bag. Colours not mentioned have a count of zero.
+ */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Cubes {
+    counts: BTreeMap<String, u32>,
+}
+
+impl Cubes {
+    fn new<'a, I: IntoIterator<Item = (&'a str, u32)>>(colors: I) -> Self {
+        let mut cubes = Self::default();
+        for (color, n) in colors {
+            *cubes.counts.entry(color.to_string()).or_default() += n;
         }
+        cubes
+    }
+
+    fn get(&self, color: &str) -> u32 {
+        self.counts.get(color).copied().unwrap_or(0)
+    }
+
+    /**
+     * The first colour (in name order) with more cubes here than in `bag`
+     */
+    fn excess(&self, bag: &Cubes) -> Option<(&str, u32)> {
+        self.counts
+            .iter()
+            .find(|(color, &n)| n > bag.get(color))
+            .map(|(color, &n)| (color.as_str(), n))
+    }
+
+    /**
+     * Product of the counts of `colors`. The empty product is 1.
+     */
+    fn power<'a, I: IntoIterator<Item = &'a str>>(&self, colors: I) -> u32 {
+        colors.into_iter().map(|c| self.get(c)).product()
     }
 }
-#[derive(Debug)]
-struct Draw {
-    reds: u32,
-    greens: u32,
-    blues: u32,
+
+/**
+ * Why a game can't be played with a given bag
+ */
+#[derive(Debug, PartialEq, Eq)]
+struct Impossible<'a> {
+    draw: usize,
+    color: &'a str,
+    drawn: u32,
+    available: u32,
 }
 
-impl Draw {
-    fn new(colors: Vec<(Color, u32)>) -> Self {
-        Self {
-            reds: colors
-                .iter()
-                .find(|c| c.0 == Color::Red)
-                .map(|c| c.1)
-                .unwrap_or(0),
-            greens: colors
-                .iter()
-                .find(|c| c.0 == Color::Green)
-                .map(|c| c.1)
-                .unwrap_or(0),
-            blues: colors
-                .iter()
-                .find(|c| c.0 == Color::Blue)
-                .map(|c| c.1)
-                .unwrap_or(0),
-        }
+impl std::fmt::Display for Impossible<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "draw {} shows {} {} but the bag only has {}",
+            self.draw + 1,
+            self.drawn,
+            self.color,
+            self.available
+        )
     }
 }
+
 #[derive(Debug)]
 struct Game {
     id: u32,
-    draws: Vec<Draw>,
+    draws: Vec<Cubes>,
 }
 
 impl Game {
-    fn new(id: u32, draws: Vec<Draw>) -> Self {
+    fn new(id: u32, draws: Vec<Cubes>) -> Self {
         Self { id, draws }
     }
 
-    fn is_possible(&self, red: u32, green: u32, blue: u32) -> bool {
-        self.draws
-            .iter()
-            .all(|d| d.reds <= red && d.greens <= green && d.blues <= blue)
+    fn is_possible(&self, bag: &Cubes) -> bool {
+        self.impossible(bag).is_none()
+    }
+
+    /**
+     * The first draw that couldn't have come out of `bag`, if any
+     */
+    fn impossible<'a>(&'a self, bag: &Cubes) -> Option<Impossible<'a>> {
+        self.draws.iter().enumerate().find_map(|(draw, d)| {
+            d.excess(bag).map(|(color, drawn)| Impossible {
+                draw,
+                color,
+                drawn,
+                available: bag.get(color),
+            })
+        })
+    }
+
+    /**
+     * The smallest bag every draw could have come from
+     */
+    fn minimal_bag(&self) -> Cubes {
+        let mut bag = Cubes::default();
+        for d in &self.draws {
+            for (color, &n) in &d.counts {
+                let max = bag.counts.entry(color.clone()).or_default();
+                *max = u32::max(*max, n);
+            }
+        }
+        bag
     }
 }
 #[derive(Debug)]
@@ -98,19 +160,14 @@ impl std::str::FromStr for Game {
     // Game 2: 1 blue, 2 green; 3 green, 4 blue, 1 red; 1 green, 1 blue
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        fn ws_digit(input: &str) -> IResult<&str, &str> {
-            delimited(space0, digit1, space0)(input)
+        fn ws_digit(input: &str) -> IResult<&str, u32> {
+            map_res(delimited(space0, digit1, space0), str::parse)(input)
         }
-        fn parse_draw_color(input: &str) -> IResult<&str, (Color, u32)> {
-            map(
-                pair(ws_digit, alt((tag("red"), tag("green"), tag("blue")))),
-                |(s1, s2)| (str::parse(s2).unwrap(), str::parse(s1).unwrap()),
-            )(input)
+        fn parse_draw_color(input: &str) -> IResult<&str, (&str, u32)> {
+            map(pair(ws_digit, alpha1), |(n, color)| (color, n))(input)
         }
-        fn parse_draws(input: &str) -> IResult<&str, Draw> {
-            map(separated_list1(tag(", "), parse_draw_color), |s| {
-                Draw::new(s)
-            })(input)
+        fn parse_draws(input: &str) -> IResult<&str, Cubes> {
+            map(separated_list1(tag(", "), parse_draw_color), Cubes::new)(input)
         }
         fn parse_game(input: &str) -> IResult<&str, Game> {
             let (input, _) = tag("Game ")(input)?;
@@ -133,9 +190,7 @@ impl PuzzleRun for Part1 {
     }
 
     fn run(&self, input: &str) -> String {
-        fn is_possible(g: &Game) -> bool {
-            g.is_possible(12, 13, 14)
-        }
+        let bag = part1_bag();
 
         let games: Vec<Game> = input
             .lines()
@@ -143,7 +198,11 @@ impl PuzzleRun for Part1 {
             .collect::<Result<Vec<Game>, _>>()
             .unwrap();
 
-        let s: u32 = games.iter().filter(|g| is_possible(g)).map(|g| g.id).sum();
+        let s: u32 = games
+            .iter()
+            .filter(|g| g.is_possible(&bag))
+            .map(|g| g.id)
+            .sum();
 
         format!("{}", s)
     }
@@ -170,31 +229,10 @@ impl PuzzleRun for Part2 {
             .collect::<Result<Vec<Game>, _>>()
             .unwrap();
 
-        let mut sum: u32 = 0;
-
-        for g in games {
-            let mut maxes: HashMap<Color, u32> =
-                [(Color::Red, 0), (Color::Green, 0), (Color::Blue, 0)].into();
-            for d in g.draws {
-                let v = maxes.get(&Color::Red).unwrap();
-                if d.reds > *v {
-                    maxes.insert(Color::Red, d.reds);
-                }
-
-                let v = maxes.get(&Color::Green).unwrap();
-                if d.greens > *v {
-                    maxes.insert(Color::Green, d.greens);
-                }
-
-                let v = maxes.get(&Color::Blue).unwrap();
-                if d.blues > *v {
-                    maxes.insert(Color::Blue, d.blues);
-                }
-            }
-            sum += maxes.get(&Color::Red).unwrap()
-                * maxes.get(&Color::Green).unwrap()
-                * maxes.get(&Color::Blue).unwrap();
-        }
+        let sum: u32 = games
+            .iter()
+            .map(|g| g.minimal_bag().power(["red", "green", "blue"]))
+            .sum();
         format!("{}", sum)
     }
 }
@@ -204,6 +242,12 @@ mod test {
 
     use super::*;
 
+    const EXAMPLE: &str = "Game 1: 3 blue, 4 red; 1 red, 2 green, 6 blue; 2 green
+Game 2: 1 blue, 2 green; 3 green, 4 blue, 1 red; 1 green, 1 blue
+Game 3: 8 green, 6 blue, 20 red; 5 blue, 4 red, 13 green; 5 green, 1 red
+Game 4: 1 green, 3 red, 6 blue; 3 green, 6 red; 3 green, 15 blue, 14 red
+Game 5: 6 red, 1 blue, 3 green; 2 blue, 1 red, 2 green";
+
     #[test]
     fn test_parse() {
         let s = "Game 1: 3 blue, 4 red; 1 red, 2 green, 6 blue; 2 green";
@@ -211,4 +255,41 @@ mod test {
         assert!(g.id == 1);
         println!("{:?}", g);
     }
+
+    #[test]
+    fn test_example() {
+        assert_eq!(Part1.run(EXAMPLE), "8");
+        assert_eq!(Part2.run(EXAMPLE), "2286");
+    }
+
+    #[test]
+    fn test_any_colors() {
+        let g: Game = "Game 7: 2 cyan, 1 magenta; 5 cyan; 3 magenta, 1 cyan, 4 yellow"
+            .parse()
+            .unwrap();
+        let min = g.minimal_bag();
+        assert_eq!(
+            min,
+            Cubes::new([("cyan", 5), ("magenta", 3), ("yellow", 4)])
+        );
+        assert_eq!(min.power(["cyan", "yellow"]), 20);
+        assert_eq!(min.power(["cyan", "black"]), 0);
+        assert!(g.is_possible(&min));
+
+        let bag = Cubes::new([("cyan", 5), ("magenta", 2), ("yellow", 9)]);
+        let why = g.impossible(&bag).unwrap();
+        assert_eq!(
+            why,
+            Impossible {
+                draw: 2,
+                color: "magenta",
+                drawn: 3,
+                available: 2
+            }
+        );
+        assert_eq!(
+            why.to_string(),
+            "draw 3 shows 3 magenta but the bag only has 2"
+        );
+    }
 }
@@ -9,12 +9,15 @@ use regex::Regex;
 use std::collections::{HashMap, VecDeque};
 
 use super::{read_file, PuzzleRun};
+#[cfg(feature = "serde")]
+use crate::dump::{write_model, DumpFormat};
 
 pub(crate) fn get_runs() -> std::vec::Vec<Box<dyn PuzzleRun>> {
     vec![Box::new(Part1)]
 }
 
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 enum Pulse {
     Low,
     High,
@@ -28,6 +31,7 @@ struct Signal {
 }
 
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 enum ModType {
     Broadcast,
     FlipFlop,
@@ -43,6 +47,7 @@ struct ModDesc<'a> {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 enum Module<'a> {
     Broadcast,
     Output,
@@ -239,6 +244,17 @@ impl PuzzleRun for Part1 {
         read_file("input/day20.txt")
     }
 
+    #[cfg(feature = "serde")]
+    fn dump_parsed(
+        &self,
+        input: &str,
+        out: &mut dyn std::io::Write,
+        format: DumpFormat,
+    ) -> anyhow::Result<()> {
+        let (graph, _, node_data, button) = parse(input);
+        write_model(&(graph, node_data, button), out, format)
+    }
+
     fn run<'a>(&self, input: &'a str) -> String {
         let mut signals = SignalQueue::new();
         let (graph, node_map, mut node_data, button) = parse(input);
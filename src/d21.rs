@@ -1,10 +1,9 @@
 use super::{read_file, PuzzleRun};
-use std::{
-    cell::Cell,
-    collections::{HashMap, HashSet, VecDeque},
-    fmt::Debug,
-    sync::Arc,
-};
+use crate::grid::{self, BitGrid};
+use crate::record::Recorder;
+use crate::render::{Image, BLACK, GREY, YELLOW};
+use crate::search::bfs_distances;
+use std::{cell::Cell, fmt::Debug, sync::Arc};
 
 pub(crate) fn get_runs() -> std::vec::Vec<Box<dyn PuzzleRun>> {
     vec![Box::new(Part1)]
@@ -47,7 +46,6 @@ struct Grid {
     height: usize,
     start: Coord,
     data: Vec<GridEntry>,
-    occupied: HashSet<Coord>,
 }
 
 impl Grid {
@@ -155,15 +153,11 @@ fn parse<'a, I: Iterator<Item = &'a str>>(input: I) -> Grid {
             })
         })
         .collect();
-    let mut occupied = HashSet::new();
-    let start = start.get();
-    occupied.insert(start);
     Grid {
         height: lines.len(),
         width: lines[0].len(),
-        start,
-        occupied,
-        data: data,
+        start: start.get(),
+        data,
     }
 }
 struct Part1;
@@ -186,45 +180,37 @@ impl PuzzleRun for Part1 {
     }
 
     fn run(&self, input: &str) -> String {
-        let mut grid = parse(input.lines());
+        let grid = parse(input.lines());
+        // plots walled off from the start can never be stood on
+        let open = grid::Grid::new_from(grid.width, grid.height, |col, row| {
+            !grid.is_rock(Coord::new(row, col))
+        })
+        .flood_fill(grid.start.col, grid.start.row, |&open| open);
+        let open = BitGrid::new_from(grid.width, grid.height, |col, row| *open.get(col, row));
+        let mut occupied = BitGrid::new(grid.width, grid.height);
+        occupied.set(grid.start.col, grid.start.row, true);
 
-        for step in 0..64 {
-            println!("{:?}", grid);
-            let mut new_cells: HashSet<Coord> = HashSet::new();
-            for idx in 0..grid.data.len() {
-                let coord = Coord::for_index(idx, grid.width);
-                if let GridEntry::Occupied = grid.data[idx] {
-                    if let Some(moved_to) = grid.can_move(coord, Dir::N) {
-                        new_cells.insert(moved_to);
-                    }
-                    if let Some(moved_to) = grid.can_move(coord, Dir::E) {
-                        new_cells.insert(moved_to);
-                    }
-                    if let Some(moved_to) = grid.can_move(coord, Dir::S) {
-                        new_cells.insert(moved_to);
-                    }
-                    if let Some(moved_to) = grid.can_move(coord, Dir::W) {
-                        new_cells.insert(moved_to);
-                    }
+        let mut recorder = Recorder::new("day21");
+        let image = |occupied: &BitGrid| {
+            Image::from_fn(grid.width, grid.height, |x, y| {
+                match (open.get(x, y), occupied.get(x, y)) {
+                    (_, true) => YELLOW,
+                    (true, false) => BLACK,
+                    (false, false) => GREY,
                 }
-            }
-            let mut new_data: Vec<GridEntry> = Vec::with_capacity(grid.data.len());
-            for i in 0..grid.data.len() {
-                new_data.push(match grid.data[i] {
-                    GridEntry::Rock => GridEntry::Rock,
-                    GridEntry::Empty | GridEntry::Occupied => {
-                        if new_cells.contains(&Coord::for_index(i, grid.width)) {
-                            GridEntry::Occupied
-                        } else {
-                            GridEntry::Empty
-                        }
-                    }
-                });
-            }
-            grid.occupied = new_cells;
-            grid.data = new_data;
+            })
+            .scale(4)
+        };
+        recorder.frame(|| image(&occupied));
+        for _ in 0..64 {
+            occupied = occupied.spread();
+            occupied &= &open;
+            recorder.frame(|| image(&occupied));
+        }
+        if let Err(e) = recorder.finish() {
+            eprintln!("couldn't save the recording: {:#}", e);
         }
-        format!("{}", grid.occupied.len())
+        format!("{}", occupied.count_ones())
     }
 }
 
@@ -236,25 +222,14 @@ impl PuzzleRun for Part2 {
     }
 
     fn run(&self, input: &str) -> String {
-        let mut grid = parse(input.lines());
-        let mut distances: HashMap<Coord, usize> = Default::default();
-        let mut q: VecDeque<(Coord, usize)> = Default::default();
-        q.push_back((Coord::new(65, 65), 0));
-
-        while let Some((c, dist)) = q.pop_front() {
-            if distances.contains_key(&c) {
-                continue;
-            }
-            distances.insert(c, dist);
-
-            for d in [Dir::N, Dir::S, Dir::E, Dir::W] {
-                if let Some(new_c) = grid.can_move(c, d) {
-                    if !distances.contains_key(&new_c) && !grid.is_rock(new_c) {
-                        q.push_back((new_c, dist + 1));
-                    }
-                }
-            }
-        }
+        let grid = parse(input.lines());
+        let distances = bfs_distances(Coord::new(65, 65), |&c| {
+            [Dir::N, Dir::S, Dir::E, Dir::W]
+                .into_iter()
+                .filter_map(|d| grid.can_move(c, d))
+                .filter(|&new_c| !grid.is_rock(new_c))
+                .collect::<Vec<_>>()
+        });
 
         let even_corners = distances
             .iter()
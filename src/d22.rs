@@ -2,6 +2,11 @@ use itertools::Itertools;
 use regex::Regex;
 
 use super::{read_file, PuzzleRun};
+#[cfg(feature = "serde")]
+use crate::dump::{write_model, DumpFormat};
+use crate::grid::{box_coords, Grid3};
+use crate::record::Recorder;
+use crate::render::{palette, Image, BLACK};
 
 struct Part1;
 
@@ -10,6 +15,7 @@ pub(crate) fn get_runs() -> std::vec::Vec<Box<dyn PuzzleRun>> {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 struct Point {
     x: u16,
     y: u16,
@@ -23,6 +29,7 @@ impl Point {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 struct Obj {
     idx: u16,
     label: String,
@@ -30,52 +37,6 @@ struct Obj {
     end: Point,
 }
 
-struct PointIterator {
-    start: Point,
-    end: Point,
-    seen: u16,
-}
-
-impl Iterator for PointIterator {
-    type Item = Point;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        let (start, end) = (self.start, self.end);
-        if start.x != end.x {
-            if start.x + self.seen > end.x {
-                None
-            } else {
-                let r = Some(Point::new(start.x + self.seen, start.y, start.z));
-                self.seen += 1;
-                r
-            }
-        } else if start.y != end.y {
-            if start.y + self.seen > end.y {
-                None
-            } else {
-                let r = Some(Point::new(start.x, start.y + self.seen, start.z));
-                self.seen += 1;
-                r
-            }
-        } else if start.z != end.z {
-            if start.z + self.seen > end.z {
-                None
-            } else {
-                let r = Some(Point::new(start.x, start.y, start.z + self.seen));
-                self.seen += 1;
-                r
-            }
-        } else {
-            if self.seen > 0 {
-                None
-            } else {
-                let r = Some(Point::new(start.x, start.y, start.z));
-                self.seen += 1;
-                r
-            }
-        }
-    }
-}
 impl Obj {
     fn new(label: String, start: Point, end: Point) -> Self {
         if start.x != end.x {
@@ -138,23 +99,32 @@ impl Obj {
     }
 
     fn points(&self) -> impl Iterator<Item = Point> {
-        PointIterator {
-            start: self.start,
-            end: self.end,
-            seen: 0,
-        }
+        let (start, end) = (self.start, self.end);
+        box_coords(
+            (
+                u16::min(start.x, end.x) as usize,
+                u16::min(start.y, end.y) as usize,
+                u16::min(start.z, end.z) as usize,
+            ),
+            (
+                u16::max(start.x, end.x) as usize,
+                u16::max(start.y, end.y) as usize,
+                u16::max(start.z, end.z) as usize,
+            ),
+        )
+        .map(|(x, y, z)| Point::new(x as u16, y as u16, z as u16))
     }
 
-    fn can_drop(&self, grid: &Grid) -> bool {
+    fn can_drop(&self, grid: &Grid3<u16>) -> bool {
         self.points()
-            .all(|p| p.z > 1 && grid.at(p.x, p.y, p.z - 1) == 0)
+            .all(|p| p.z > 1 && at(grid, p.x, p.y, p.z - 1) == 0)
     }
-    fn drop(&mut self, grid: &mut Grid) {
+    fn drop(&mut self, grid: &mut Grid3<u16>) {
         self.start.z -= 1;
         self.end.z -= 1;
         for p in self.points() {
-            grid.set(p.x, p.y, p.z, 0);
-            grid.set(p.x, p.y, p.z, self.idx);
+            set(grid, p.x, p.y, p.z, 0);
+            set(grid, p.x, p.y, p.z, self.idx);
         }
     }
 }
@@ -186,92 +156,77 @@ impl std::str::FromStr for Obj {
     }
 }
 
-#[derive(Debug)]
-struct Grid {
-    data: Box<[u16]>,
-    x_size: usize,
-    y_size: usize,
-    z_size: usize,
+/**
+ * Bricks numbered from 1 in input order, sorted lowest first
+ */
+fn parse(input: &str) -> Vec<Obj> {
+    let mut objs: Vec<Obj> = input
+        .lines()
+        .enumerate()
+        .map(|(idx, s)| {
+            let mut obj: Obj = s.parse().unwrap();
+            let idx: u16 = idx.try_into().unwrap();
+            obj.idx = idx + 1;
+            obj
+        })
+        .collect();
+    objs.sort_by_key(|o| u16::min(o.start.z, o.end.z) as i32);
+    objs
 }
 
-impl Grid {
-    fn _idx(x_size: usize, y_size: usize, z_size: usize, x: u16, y: u16, z: u16) -> usize {
-        (z as usize) * x_size * y_size + (y as usize) * x_size + (x as usize)
-    }
-
-    fn idx(&self, x: u16, y: u16, z: u16) -> usize {
-        Self::_idx(self.x_size, self.y_size, self.z_size, x, y, z)
-    }
-    fn new(objs: &Vec<Obj>) -> Self {
-        let Some((ll, ur)) = objs
-            .iter()
-            .map(|o| {
-                (
-                    Point::new(
-                        u16::min(o.start.x, o.end.x),
-                        u16::min(o.start.y, o.end.y),
-                        u16::min(o.start.z, o.end.z),
-                    ),
-                    Point::new(
-                        u16::max(o.start.x, o.end.x),
-                        u16::max(o.start.y, o.end.y),
-                        u16::max(o.start.z, o.end.z),
-                    ),
-                )
-            })
-            .reduce(|acc, v| {
-                (
-                    Point::new(
-                        u16::min(acc.0.x, v.0.x),
-                        u16::min(acc.0.y, v.0.y),
-                        u16::min(acc.0.z, v.0.z),
-                    ),
-                    Point::new(
-                        u16::max(acc.1.x, v.1.x),
-                        u16::max(acc.1.y, v.1.y),
-                        u16::max(acc.1.z, v.1.z),
-                    ),
-                )
-            })
-        else {
-            panic!()
-        };
-
-        let (x_size, y_size, z_size) = (ur.x as usize + 1, ur.y as usize + 1, ur.z as usize + 1);
-        let mut v: Vec<u16> = Vec::with_capacity(x_size * y_size * z_size);
-        v.extend(std::iter::repeat(0).take(x_size * y_size * z_size));
-        for o in objs {
-            for p in o.points() {
-                v[Self::_idx(x_size, y_size, z_size, p.x, p.y, p.z)] = o.idx;
-            }
-        }
-
-        Self {
-            data: v.into_boxed_slice(),
-            x_size,
-            y_size,
-            z_size,
+fn build_grid(objs: &[Obj]) -> Grid3<u16> {
+    let Some(ur) = objs
+        .iter()
+        .map(|o| {
+            Point::new(
+                u16::max(o.start.x, o.end.x),
+                u16::max(o.start.y, o.end.y),
+                u16::max(o.start.z, o.end.z),
+            )
+        })
+        .reduce(|acc, v| {
+            Point::new(
+                u16::max(acc.x, v.x),
+                u16::max(acc.y, v.y),
+                u16::max(acc.z, v.z),
+            )
+        })
+    else {
+        panic!()
+    };
+
+    let mut grid = Grid3::new_with(ur.x as usize + 1, ur.y as usize + 1, ur.z as usize + 1, 0);
+    for o in objs {
+        for p in o.points() {
+            set(&mut grid, p.x, p.y, p.z, o.idx);
         }
     }
+    grid
+}
 
-    fn at(&self, x: u16, y: u16, z: u16) -> u16 {
-        let id = self.idx(x, y, z);
-        self.data[self.idx(x, y, z)]
-    }
+/**
+ * Looking along y, with the ground at the bottom: each column shows the
+ * nearest brick
+ */
+fn side_view(grid: &Grid3<u16>) -> Image {
+    Image::from_fn(grid.x_size, grid.z_size, |x, row| {
+        let z = grid.z_size - 1 - row;
+        (0..grid.y_size)
+            .map(|y| *grid.get((x, y, z)).unwrap())
+            .find(|&id| id != 0)
+            .map_or(BLACK, |id| palette(id as usize))
+    })
+    .scale(4)
+}
 
-    fn set(&mut self, x: u16, y: u16, z: u16, val: u16) {
-        self.data[self.idx(x, y, z)] = val;
-    }
+fn at(grid: &Grid3<u16>, x: u16, y: u16, z: u16) -> u16 {
+    *grid.get((x as usize, y as usize, z as usize)).unwrap()
+}
 
-    fn print_z(&self, z: u16) {
-        for y in (0u16..self.y_size as u16).rev() {
-            for x in (0u16..self.x_size as u16).rev() {
-                print!("\t|{}|\t", self.at(x, y, z));
-            }
-            println!("");
-        }
-    }
+fn set(grid: &mut Grid3<u16>, x: u16, y: u16, z: u16, val: u16) {
+    grid.set((x as usize, y as usize, z as usize), val).unwrap();
 }
+
 impl PuzzleRun for Part1 {
     fn input_data(&self) -> anyhow::Result<&str> {
         Ok("1,0,1~1,2,1
@@ -285,26 +240,32 @@ impl PuzzleRun for Part1 {
         //        read_file("input/day22.txt")
     }
 
+    #[cfg(feature = "serde")]
+    fn dump_parsed(
+        &self,
+        input: &str,
+        out: &mut dyn std::io::Write,
+        format: DumpFormat,
+    ) -> anyhow::Result<()> {
+        write_model(&parse(input), out, format)
+    }
+
     fn run(&self, input: &str) -> String {
-        let mut objs: Vec<Obj> = input
-            .lines()
-            .enumerate()
-            .map(|(idx, s)| {
-                let mut obj: Obj = s.parse().unwrap();
-                let idx: u16 = idx.try_into().unwrap();
-                obj.idx = idx + 1;
-                obj
-            })
-            .collect();
-        objs.sort_by_key(|o| u16::min(o.start.z, o.end.z) as i32);
-        let mut grid = Grid::new(&objs);
+        let mut objs = parse(input);
+        let mut grid = build_grid(&objs);
+        let mut recorder = Recorder::new("day22");
 
+        recorder.frame(|| side_view(&grid));
         for o in objs.iter_mut() {
             while o.can_drop(&grid) {
                 println!("dropping obj [{}]", o.label);
                 o.drop(&mut grid);
+                recorder.frame(|| side_view(&grid));
             }
         }
+        if let Err(e) = recorder.finish() {
+            eprintln!("couldn't save the recording: {:#}", e);
+        }
 
         "FOO".to_string()
     }
@@ -324,32 +285,18 @@ mod test {
 
     #[test]
     fn test_grid() {
-        let mut objs: Vec<Obj> = Part1
-            .input_data()
-            .unwrap()
-            .lines()
-            .enumerate()
-            .map(|(idx, s)| {
-                let mut obj: Obj = s.parse().unwrap();
-                let idx: u16 = idx.try_into().unwrap();
-                obj.idx = idx + 1;
-                obj
-            })
-            .collect();
+        let objs = parse(Part1.input_data().unwrap());
         for o in &objs {
             println!("{}: {}", o.label, o.idx);
         }
-        objs.sort_by_key(|o| u16::min(o.start.z, o.end.z) as i32);
-        let grid = Grid::new(&objs);
+        let grid = build_grid(&objs);
 
-        println!("z = 0");
-        grid.print_z(0);
-        println!("z = 1");
-        grid.print_z(1);
-        println!("z = 2");
-        grid.print_z(2);
+        for z in 0..3 {
+            println!("z = {}", z);
+            println!("{}", grid.layer(z).unwrap());
+        }
 
-        assert_eq!(grid.at(1, 0, 1), 1);
+        assert_eq!(at(&grid, 1, 0, 1), 1);
     }
     #[test]
     fn test_point_iter() {
@@ -399,4 +346,17 @@ mod test {
             ranges[2].last().unwrap()
         );
     }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_dump_round_trip() {
+        let input = Part1.input_data().unwrap();
+        let mut out = vec![];
+        Part1
+            .dump_parsed(input, &mut out, DumpFormat::Json)
+            .unwrap();
+        let objs: Vec<Obj> = serde_json::from_slice(&out).unwrap();
+        assert_eq!(objs.len(), input.lines().count());
+        assert_eq!(objs[0].start, parse(input)[0].start);
+    }
 }
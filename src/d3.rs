@@ -1,177 +1,209 @@
 use crate::{read_file, PuzzleRun};
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap};
 
 pub(crate) fn get_runs() -> Vec<Box<dyn PuzzleRun>> {
-    vec![Box::new(Part2)]
+    vec![Box::new(Part1), Box::new(Part2)]
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-struct LineNo(u32);
+/**
+ * Which characters in a schematic count as symbols. Anything that is neither
+ * a digit nor a symbol is blank, like `.`.
+ */
+#[derive(Debug, Clone, Default)]
+enum SymbolRule {
+    /// Everything except digits and `.`
+    #[default]
+    AnyPunctuation,
+    Only(BTreeSet<char>),
+    Except(BTreeSet<char>),
+}
 
-impl LineNo {
-    fn new(l: usize) -> Self {
-        Self(l.try_into().unwrap())
+impl SymbolRule {
+    fn only(chars: &str) -> Self {
+        Self::Only(chars.chars().collect())
     }
 
-    fn is_first(&self) -> bool {
-        self.0 == 0
+    fn except(chars: &str) -> Self {
+        Self::Except(chars.chars().collect())
     }
 
-    fn prev(&self) -> Self {
-        if self.0 == 0 {
-            panic!();
-        } else {
-            LineNo(self.0 - 1)
+    fn is_symbol(&self, c: char) -> bool {
+        if c.is_ascii_digit() || c.is_whitespace() {
+            return false;
+        }
+        match self {
+            Self::AnyPunctuation => c != '.',
+            Self::Only(chars) => chars.contains(&c),
+            Self::Except(chars) => c != '.' && !chars.contains(&c),
         }
-    }
-
-    fn next(&self) -> Self {
-        LineNo(self.0 + 1)
     }
 }
-#[derive(Debug)]
+
+/**
+ * A number in the schematic, covering columns `start_col..=end_col`
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
 struct PartNo {
     part_no: u32,
-    line: LineNo,
+    line: usize,
     start_col: usize,
     end_col: usize,
 }
 
-impl PartNo {
-    fn new(part_no: u32, line: LineNo, start_col: usize, end_col: usize) -> Self {
-        Self {
-            part_no,
-            line,
-            start_col,
-            end_col,
-        }
-    }
-}
-
-#[derive(Debug)]
-struct Special {
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Symbol {
     code: char,
-    line: LineNo,
+    line: usize,
     col: usize,
-    parts: HashSet<u32>,
-}
-
-impl Special {
-    fn new(code: char, line: LineNo, col: usize) -> Self {
-        Self {
-            code,
-            line,
-            col,
-            parts: HashSet::new(),
-        }
-    }
-}
-struct Part1;
-
-fn add_part(
-    part_nos: &mut Vec<PartNo>,
-    line: &str,
-    line_no: LineNo,
-    start_col: usize,
-    end_col: usize,
-) {
-    let part_no: u32 = line[start_col..end_col].parse().unwrap();
-    part_nos.push(PartNo::new(part_no, line_no, start_col, end_col - 1));
 }
 
-fn is_neighbor(part: &PartNo, special: &mut Special) -> bool {
-    let is_it = !(part.end_col < special.col - 1 || part.start_col > special.col + 1);
-    if is_it {
-        special.parts.insert(part.part_no);
-    }
-    is_it
+/**
+ * Every number and symbol in a schematic, with the bipartite graph of which
+ * numbers touch which symbols (including diagonally) built up front, so
+ * queries don't need to rescan the text. Numbers and symbols are referred to
+ * by their index in `numbers` and `symbols`.
+ */
+#[derive(Debug)]
+struct Schematic {
+    numbers: Vec<PartNo>,
+    symbols: Vec<Symbol>,
+    number_symbols: Vec<Vec<usize>>,
+    symbol_numbers: Vec<Vec<usize>>,
 }
 
-fn _run(input: &str) -> (Vec<PartNo>, HashMap<LineNo, Vec<Special>>) {
-    let mut specials: HashMap<LineNo, Vec<Special>> = HashMap::new();
-    let mut part_nos: Vec<PartNo> = Vec::new();
-
-    for (line_no, line) in input.lines().enumerate() {
-        let line = line.trim();
-        let mut start_col: Option<usize> = None;
-
-        for (col_no, c) in line.chars().enumerate() {
-            if c == '.' {
-                if let Some(sc) = start_col.take() {
-                    let line_no = LineNo::new(line_no);
-                    println!(
-                        "at line {:?}, reading string '{}'",
-                        line_no,
-                        &line[sc..col_no]
-                    );
-                    add_part(&mut part_nos, line, line_no, sc, col_no);
+impl Schematic {
+    fn new(input: &str, rule: &SymbolRule) -> Self {
+        let mut numbers = vec![];
+        let mut symbols = vec![];
+        for (line, text) in input.lines().map(str::trim).enumerate() {
+            let mut current: Option<PartNo> = None;
+            for (col, c) in text.chars().enumerate() {
+                if let Some(d) = c.to_digit(10) {
+                    let n = current.get_or_insert(PartNo {
+                        part_no: 0,
+                        line,
+                        start_col: col,
+                        end_col: col,
+                    });
+                    n.part_no = n.part_no * 10 + d;
+                    n.end_col = col;
+                    continue;
                 }
-            } else if c.is_ascii_digit() {
-                if start_col.is_none() {
-                    start_col = Some(col_no);
-                }
-            } else {
-                let e = specials.entry(LineNo::new(line_no));
-                e.or_default()
-                    .push(Special::new(c, LineNo::new(line_no), col_no));
-                if let Some(sc) = start_col.take() {
-                    let line_no = LineNo::new(line_no);
-                    add_part(&mut part_nos, line, line_no, sc, col_no);
-                }
-            }
-        }
-        if let Some(sc) = start_col.take() {
-            // ended the line on a number
-            let line_no = LineNo::new(line_no);
-            add_part(&mut part_nos, line, line_no, sc, line.len());
-        }
-    }
-
-    part_nos.retain(|mut part| {
-        let mut is_n = false;
-        if let Some(v) = specials.get_mut(&part.line) {
-            for s in v.iter_mut() {
-                if is_neighbor(&part, s) {
-                    is_n = true;
+                numbers.extend(current.take());
+                if rule.is_symbol(c) {
+                    symbols.push(Symbol { code: c, line, col });
                 }
             }
+            numbers.extend(current);
         }
 
-        if !part.line.is_first() {
-            if let Some(v) = specials.get_mut(&(part.line.prev())) {
-                for s in v.iter_mut() {
-                    if is_neighbor(&part, s) {
-                        is_n = true;
+        let at: HashMap<(usize, usize), usize> = symbols
+            .iter()
+            .enumerate()
+            .map(|(i, s)| ((s.line, s.col), i))
+            .collect();
+        let mut number_symbols = vec![vec![]; numbers.len()];
+        let mut symbol_numbers = vec![vec![]; symbols.len()];
+        for (i, n) in numbers.iter().enumerate() {
+            for line in n.line.saturating_sub(1)..=n.line + 1 {
+                for col in n.start_col.saturating_sub(1)..=n.end_col + 1 {
+                    if let Some(&s) = at.get(&(line, col)) {
+                        number_symbols[i].push(s);
+                        symbol_numbers[s].push(i);
                     }
                 }
             }
         }
-        if let Some(v) = specials.get_mut(&part.line.next()) {
-            for s in v.iter_mut() {
-                if is_neighbor(&part, s) {
-                    is_n = true;
-                }
-            }
+
+        Self {
+            numbers,
+            symbols,
+            number_symbols,
+            symbol_numbers,
         }
-        println!("removing part {}", part.part_no);
-        is_n
-    });
-
-    println!(
-        "retained parts {:?}",
-        part_nos.iter().map(|p| p.part_no).collect::<Vec<u32>>()
-    );
-    (part_nos, specials)
+    }
+
+    /**
+     * Numbers touching at least one symbol
+     */
+    fn part_numbers(&self) -> impl Iterator<Item = &PartNo> {
+        self.numbers_where(|s| !s.is_empty())
+    }
+
+    fn isolated(&self) -> impl Iterator<Item = &PartNo> {
+        self.numbers_where(|s| s.is_empty())
+    }
+
+    /**
+     * Numbers touching at least one symbol drawn as `code`
+     */
+    fn next_to(&self, code: char) -> impl Iterator<Item = &PartNo> {
+        self.numbers_where(move |s| s.iter().any(|&i| self.symbols[i].code == code))
+    }
+
+    /**
+     * Symbols touching exactly `k` numbers, along with those numbers
+     */
+    fn with_degree(&self, k: usize) -> impl Iterator<Item = (&Symbol, Vec<&PartNo>)> {
+        self.symbols
+            .iter()
+            .zip(&self.symbol_numbers)
+            .filter(move |(_, n)| n.len() == k)
+            .map(|(s, n)| (s, n.iter().map(|&i| &self.numbers[i]).collect()))
+    }
+
+    /**
+     * `*` symbols next to exactly two numbers, and the product of the two
+     */
+    fn gear_ratios(&self) -> impl Iterator<Item = u32> + '_ {
+        self.with_degree(2)
+            .filter(|(s, _)| s.code == '*')
+            .map(|(_, n)| n.iter().map(|p| p.part_no).product())
+    }
+
+    fn numbers_where<'a, F>(&'a self, keep: F) -> impl Iterator<Item = &'a PartNo>
+    where
+        F: Fn(&[usize]) -> bool + 'a,
+    {
+        self.numbers
+            .iter()
+            .zip(&self.number_symbols)
+            .filter(move |(_, s)| keep(s))
+            .map(|(n, _)| n)
+    }
+}
+
+/**
+ * Print which numbers touch each kind of symbol and which touch none, with
+ * the symbols limited to `only` or to everything but `except` when given
+ */
+pub(crate) fn report(only: Option<&str>, except: Option<&str>) -> anyhow::Result<()> {
+    let rule = match (only, except) {
+        (Some(chars), _) => SymbolRule::only(chars),
+        (None, Some(chars)) => SymbolRule::except(chars),
+        (None, None) => SymbolRule::default(),
+    };
+    let schematic = Schematic::new(read_file("input/day3.txt")?, &rule);
+    let codes: BTreeSet<char> = schematic.symbols.iter().map(|s| s.code).collect();
+    for code in codes {
+        let numbers: Vec<u32> = schematic.next_to(code).map(|p| p.part_no).collect();
+        println!("{} touches {} numbers: {:?}", code, numbers.len(), numbers);
+    }
+    let isolated: Vec<u32> = schematic.isolated().map(|p| p.part_no).collect();
+    println!("{} numbers touch no symbol: {:?}", isolated.len(), isolated);
+    Ok(())
 }
 
+struct Part1;
+
 impl PuzzleRun for Part1 {
     fn input_data(&self) -> anyhow::Result<&str> {
         read_file("input/day3.txt")
     }
 
     fn run(&self, input: &str) -> String {
-        let (part_nos, specials) = _run(input);
-        let sum: u32 = part_nos.iter().map(|p| p.part_no).sum();
+        let schematic = Schematic::new(input, &SymbolRule::default());
+        let sum: u32 = schematic.part_numbers().map(|p| p.part_no).sum();
 
         format!("{}", sum)
     }
@@ -197,19 +229,8 @@ impl PuzzleRun for Part2 {
     }
 
     fn run(&self, input: &str) -> String {
-        let (part_nos, specials) = _run(input);
-
-        let v: u32 = specials
-            .values()
-            .flat_map(|v| v.iter().filter(|s| s.parts.len() == 2))
-            .map(|s| {
-                s.parts
-                    .iter()
-                    .copied()
-                    .reduce(|acc: u32, e: u32| -> u32 { acc * e })
-                    .unwrap()
-            })
-            .sum();
+        let schematic = Schematic::new(input, &SymbolRule::default());
+        let v: u32 = schematic.gear_ratios().sum();
 
         format!("{}", v)
     }
@@ -220,8 +241,54 @@ mod test {
 
     use super::*;
 
+    const EXAMPLE: &str = "467..114..
+        ...*......
+        ..35..633.
+        ......#...
+        617*......
+        .....+.58.
+        ..592.....
+        ......755.
+        ...$.*....
+        .664.598..";
+
+    fn values<'a>(numbers: impl Iterator<Item = &'a PartNo>) -> Vec<u32> {
+        numbers.map(|p| p.part_no).collect()
+    }
+
     #[test]
     fn test_part1() {
         Part1.run(Part1.input_data().unwrap());
     }
+
+    #[test]
+    fn test_example() {
+        assert_eq!(Part1.run(EXAMPLE), "4361");
+        assert_eq!(Part2.run(EXAMPLE), "467835");
+    }
+
+    #[test]
+    fn test_queries() {
+        let schematic = Schematic::new(EXAMPLE, &SymbolRule::default());
+        assert_eq!(values(schematic.isolated()), vec![114, 58]);
+        assert_eq!(values(schematic.next_to('#')), vec![633]);
+        assert_eq!(values(schematic.next_to('*')), vec![467, 35, 617, 755, 598]);
+
+        let single: Vec<char> = schematic.with_degree(1).map(|(s, _)| s.code).collect();
+        assert_eq!(single, vec!['#', '*', '+', '$']);
+        let (gear, parts) = schematic.with_degree(2).next().unwrap();
+        assert_eq!((gear.line, gear.col), (1, 3));
+        assert_eq!(values(parts.into_iter()), vec![467, 35]);
+    }
+
+    #[test]
+    fn test_symbol_rule() {
+        let schematic = Schematic::new(EXAMPLE, &SymbolRule::only("*"));
+        assert_eq!(schematic.symbols.len(), 3);
+        assert_eq!(values(schematic.isolated()), vec![114, 633, 58, 592, 664]);
+
+        let schematic = Schematic::new(EXAMPLE, &SymbolRule::except("$+"));
+        assert_eq!(values(schematic.next_to('$')), Vec::<u32>::new());
+        assert_eq!(values(schematic.isolated()), vec![114, 58, 592, 664]);
+    }
 }
@@ -1,6 +1,6 @@
 use crate::{read_file, PuzzleRun};
-use std::collections::VecDeque;
 
+use num_traits::PrimInt;
 use regex::Regex;
 
 pub(crate) fn get_runs() -> Vec<Box<dyn PuzzleRun>> {
@@ -79,6 +79,90 @@ impl PuzzleRun for Part1 {
     }
 }
 
+/**
+ * What to do when a card wins copies of more cards than there are left in
+ * the table
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum PastEnd {
+    /// Only copy the cards that exist
+    #[default]
+    Clip,
+    Error,
+}
+
+/**
+ * How many copies of each card end up being scratched, counted in `N`.
+ * Copies won by a card only ever go to later cards, so a single pass in
+ * order works: each card's count is final once every earlier card is done.
+ */
+#[derive(Debug)]
+struct Cascade<N> {
+    ids: Vec<u32>,
+    wins: Vec<usize>,
+    copies: Vec<N>,
+}
+
+impl<N: PrimInt> Cascade<N> {
+    fn new(cards: &[Card], past_end: PastEnd) -> anyhow::Result<Self> {
+        let n = cards.len();
+        let wins: Vec<usize> = cards.iter().map(|c| c.count_winners() as usize).collect();
+        let mut copies = Vec::with_capacity(n);
+        // copies held by earlier cards that are still handing out copies,
+        // and the amount to drop from that when reaching each card
+        let mut running = N::zero();
+        let mut expire = vec![N::zero(); n + 1];
+        let overflow = || anyhow::anyhow!("copy count doesn't fit");
+
+        for (i, card) in cards.iter().enumerate() {
+            running = running - expire[i];
+            let count = N::one().checked_add(&running).ok_or_else(overflow)?;
+            copies.push(count);
+
+            let mut end = i + 1 + wins[i];
+            if end > n {
+                match past_end {
+                    PastEnd::Clip => end = n,
+                    PastEnd::Error => anyhow::bail!(
+                        "card {} wins {} cards but only {} follow it",
+                        card.id,
+                        wins[i],
+                        n - i - 1
+                    ),
+                }
+            }
+            if end > i + 1 {
+                running = running.checked_add(&count).ok_or_else(overflow)?;
+                expire[end] = expire[end].checked_add(&count).ok_or_else(overflow)?;
+            }
+        }
+
+        Ok(Self {
+            ids: cards.iter().map(|c| c.id).collect(),
+            wins,
+            copies,
+        })
+    }
+
+    /**
+     * Each card's id, number of winning numbers and number of copies
+     */
+    fn breakdown(&self) -> impl Iterator<Item = (u32, usize, N)> + '_ {
+        self.ids
+            .iter()
+            .zip(&self.wins)
+            .zip(&self.copies)
+            .map(|((&id, &wins), &copies)| (id, wins, copies))
+    }
+
+    fn total(&self) -> anyhow::Result<N> {
+        self.copies.iter().try_fold(N::zero(), |acc, &c| {
+            acc.checked_add(&c)
+                .ok_or_else(|| anyhow::anyhow!("total doesn't fit"))
+        })
+    }
+}
+
 struct Part2;
 
 impl PuzzleRun for Part2 {
@@ -96,23 +180,9 @@ impl PuzzleRun for Part2 {
 
     fn run(&self, input: &str) -> String {
         let cards: Vec<Card> = input.lines().map(|s| s.parse().unwrap()).collect();
-        let card_winners: Vec<(usize, u32)> = cards
-            .iter()
-            .enumerate()
-            .map(|(i, c)| (i, c.count_winners()))
-            .collect();
-
-        let mut q: VecDeque<(usize, u32)> = card_winners.iter().copied().collect();
-        let mut card_count: u32 = 0;
-
-        while !q.is_empty() {
-            card_count += 1;
-            let (card, mut winners) = q.pop_front().unwrap();
-            while winners > 0 {
-                q.push_back(card_winners[card + winners as usize]);
-                winners -= 1;
-            }
-        }
+        let card_count: u64 = Cascade::new(&cards, PastEnd::Error)
+            .and_then(|c| c.total())
+            .unwrap();
         format!("{}", card_count)
     }
 }
@@ -145,4 +215,64 @@ mod test {
     fn test_part2() {
         println!("{}", Part2.run(Part2.input_data().unwrap()))
     }
+
+    const EXAMPLE: &str = "Card 1: 41 48 83 86 17 | 83 86  6 31 17  9 48 53
+Card 2: 13 32 20 16 61 | 61 30 68 82 17 32 24 19
+Card 3:  1 21 53 59 44 | 69 82 63 72 16 21 14  1
+Card 4: 41 92 73 84 69 | 59 84 76 51 58  5 54 83
+Card 5: 87 83 26 28 32 | 88 30 70 12 93 22 82 36
+Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11";
+
+    fn cards(input: &str) -> Vec<Card> {
+        input.lines().map(|s| s.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_cascade() {
+        let cascade: Cascade<u64> = Cascade::new(&cards(EXAMPLE), PastEnd::Error).unwrap();
+        let breakdown: Vec<_> = cascade.breakdown().collect();
+        assert_eq!(
+            breakdown,
+            vec![
+                (1, 4, 1),
+                (2, 2, 2),
+                (3, 2, 4),
+                (4, 1, 8),
+                (5, 0, 14),
+                (6, 0, 1)
+            ]
+        );
+        assert_eq!(cascade.total().unwrap(), 30);
+
+        let wide: Cascade<u128> = Cascade::new(&cards(EXAMPLE), PastEnd::Clip).unwrap();
+        assert_eq!(wide.total().unwrap(), 30);
+    }
+
+    #[test]
+    fn test_past_end() {
+        // cards 2 and 3 each win one more card than follows them
+        let input = "Card 1: 1 2 | 1 3
+Card 2: 1 2 | 1 2
+Card 3: 5 | 5";
+        let clipped: Cascade<u64> = Cascade::new(&cards(input), PastEnd::Clip).unwrap();
+        let copies: Vec<u64> = clipped.breakdown().map(|(_, _, c)| c).collect();
+        assert_eq!(copies, vec![1, 2, 3]);
+
+        let err = Cascade::<u64>::new(&cards(input), PastEnd::Error).unwrap_err();
+        assert_eq!(err.to_string(), "card 2 wins 2 cards but only 1 follow it");
+    }
+
+    #[test]
+    fn test_overflow() {
+        // every card wins a copy of all the cards after it, so counts double
+        let input: String = (1..=10)
+            .map(|i| format!("Card {}: 1 2 3 4 5 6 7 8 9 10 | 1 2 3 4 5 6 7 8 9 10\n", i))
+            .collect();
+        let cascade: Cascade<u16> = Cascade::new(&cards(&input), PastEnd::Clip).unwrap();
+        assert_eq!(cascade.breakdown().last().unwrap(), (10, 10, 512));
+        assert_eq!(cascade.total().unwrap(), 1023);
+
+        assert!(Cascade::<u8>::new(&cards(&input), PastEnd::Clip).is_err());
+        assert!(Cascade::<u16>::new(&cards(&input), PastEnd::Error).is_err());
+    }
 }
@@ -1,16 +1,22 @@
+#[cfg(feature = "serde")]
+use crate::dump::{write_model, DumpFormat};
 use crate::{read_file, PuzzleRun};
 use itertools::Itertools;
-use rayon::prelude::*;
+use petgraph::{
+    algo::all_simple_paths,
+    graph::{DiGraph, NodeIndex},
+};
 
-use std::collections::HashMap;
+use std::{collections::HashMap, fmt::Display, ops::Range};
 
 use regex::Regex;
 
 pub(crate) fn get_runs() -> Vec<Box<dyn PuzzleRun>> {
-    vec![Box::new(Part2)]
+    vec![Box::new(Part1), Box::new(Part2)]
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 struct MapRange {
     src_base: u64,
     dest_base: u64,
@@ -25,9 +31,22 @@ impl MapRange {
             len,
         }
     }
+
+    fn src(&self) -> Range<u64> {
+        self.src_base..self.src_base + self.len
+    }
+
+    fn dest(&self) -> Range<u64> {
+        self.dest_base..self.dest_base + self.len
+    }
+
+    fn shift(&self, r: Range<u64>) -> Range<u64> {
+        r.start - self.src_base + self.dest_base..r.end - self.src_base + self.dest_base
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 struct Map {
     ranges: Vec<MapRange>,
 }
@@ -49,11 +68,170 @@ impl Map {
         let Some(len) = v.next() else {
             panic!();
         };
-        self.ranges.push(MapRange::new(src_start, dest_start, len));
-        self.ranges.sort_unstable_by_key(|r| r.src_base);
+        let at = self.ranges.partition_point(|r| r.src_base < src_start);
+        self.ranges
+            .insert(at, MapRange::new(src_start, dest_start, len));
+    }
+
+    /**
+     * The same mapping with the gaps between ranges filled in as explicit
+     * identity pieces
+     */
+    fn to_piecewise(&self) -> Piecewise {
+        let mut pieces = vec![];
+        let mut next = 0;
+        for r in &self.ranges {
+            if next < r.src_base {
+                pieces.push(MapRange::new(next, next, r.src_base - next));
+            }
+            pieces.push(*r);
+            next = r.src().end;
+        }
+        pieces.push(MapRange::new(next, next, u64::MAX - next));
+        Piecewise { pieces }
+    }
+
+    /**
+     * Where every number in `r` goes, split at the edges of the ranges, with
+     * any part not covered by a range passed through unchanged
+     */
+    fn apply_interval(&self, r: Range<u64>, out: &mut Vec<Range<u64>>) {
+        let mut start = r.start;
+        for range in &self.ranges {
+            if start >= r.end {
+                break;
+            }
+            let src = range.src();
+            if src.end <= start {
+                continue;
+            }
+            if src.start >= r.end {
+                break;
+            }
+            if start < src.start {
+                out.push(start..src.start);
+                start = src.start;
+            }
+            let end = u64::min(r.end, src.end);
+            out.push(range.shift(start..end));
+            start = end;
+        }
+        if start < r.end {
+            out.push(start..r.end);
+        }
+    }
+}
+
+/**
+ * A mapping made of linear pieces that between them cover every number
+ * below `u64::MAX` exactly once, sorted by source, so any number can be
+ * looked up with a binary search
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Piecewise {
+    pieces: Vec<MapRange>,
+}
+
+impl Piecewise {
+    fn identity() -> Self {
+        Self {
+            pieces: vec![MapRange::new(0, 0, u64::MAX)],
+        }
+    }
+
+    fn piece_for(&self, n: u64) -> &MapRange {
+        &self.pieces[self.pieces.partition_point(|p| p.src_base <= n) - 1]
+    }
+
+    fn apply(&self, n: u64) -> u64 {
+        let p = self.piece_for(n);
+        n - p.src_base + p.dest_base
     }
+
+    /**
+     * Every number that maps to `n`, smallest first
+     */
+    fn inverse(&self, n: u64) -> Vec<u64> {
+        let mut found: Vec<u64> = self
+            .pieces
+            .iter()
+            .filter(|p| p.dest().contains(&n))
+            .map(|p| n - p.dest_base + p.src_base)
+            .collect();
+        found.sort_unstable();
+        found
+    }
+
+    /**
+     * `self` followed by `next`, as one mapping
+     */
+    fn then(&self, next: &Piecewise) -> Piecewise {
+        let mut pieces = vec![];
+        for p in &self.pieces {
+            let end = p.dest().end;
+            let mut d = p.dest_base;
+            let mut i = next.pieces.partition_point(|q| q.src_base <= d) - 1;
+            while d < end {
+                let q = &next.pieces[i];
+                let stop = u64::min(end, q.src().end);
+                pieces.push(MapRange::new(
+                    d - p.dest_base + p.src_base,
+                    d - q.src_base + q.dest_base,
+                    stop - d,
+                ));
+                d = stop;
+                i += 1;
+            }
+        }
+        pieces.sort_unstable_by_key(|p| p.src_base);
+
+        // join neighbours that turned out to shift by the same amount
+        let mut joined: Vec<MapRange> = Vec::with_capacity(pieces.len());
+        for p in pieces {
+            match joined.last_mut() {
+                Some(last) if last.dest().end == p.dest_base => last.len += p.len,
+                _ => joined.push(p),
+            }
+        }
+        Piecewise { pieces: joined }
+    }
+}
+
+impl Display for Piecewise {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{:>25} {:>25} {:>12}", "from", "to", "shift")?;
+        for p in &self.pieces {
+            let (src, dest) = (p.src(), p.dest());
+            writeln!(
+                f,
+                "{:>25} {:>25} {:>+12}",
+                format!("{}..{}", src.start, src.end),
+                format!("{}..{}", dest.start, dest.end),
+                p.dest_base as i128 - p.src_base as i128
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/**
+ * Sort intervals and join any that overlap or touch, dropping empty ones
+ */
+fn merge(mut intervals: Vec<Range<u64>>) -> Vec<Range<u64>> {
+    intervals.retain(|r| !r.is_empty());
+    intervals.sort_unstable_by_key(|r| r.start);
+    let mut merged: Vec<Range<u64>> = Vec::with_capacity(intervals.len());
+    for r in intervals {
+        match merged.last_mut() {
+            Some(last) if r.start <= last.end => last.end = u64::max(last.end, r.end),
+            _ => merged.push(r),
+        }
+    }
+    merged
 }
 
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 struct MapSeq {
     maps: Vec<Map>,
 }
@@ -63,6 +241,11 @@ impl MapSeq {
         MapSeq { maps }
     }
 
+    /**
+     * One number through every map in turn, the slow way, to check
+     * `compose` and `apply_intervals` against
+     */
+    #[cfg(test)]
     fn apply(&self, n: u64) -> u64 {
         let mut t = n;
         for m in &self.maps {
@@ -77,69 +260,178 @@ impl MapSeq {
         t
     }
 
-    fn maybe_mins(&self, start: u64, len: u64) -> Vec<u64> {
-        let mut v = vec![start];
-        for r in &self.maps[0].ranges {
-            if r.src_base > start && r.src_base < r.src_base + len {
-                v.push(r.src_base);
+    /**
+     * All the maps folded into one
+     */
+    fn compose(&self) -> Piecewise {
+        self.maps
+            .iter()
+            .fold(Piecewise::identity(), |f, m| f.then(&m.to_piecewise()))
+    }
+
+    /**
+     * Every location reached from a seed in `seeds`, as sorted, disjoint
+     * intervals
+     */
+    fn apply_intervals(&self, seeds: Vec<Range<u64>>) -> Vec<Range<u64>> {
+        self.maps.iter().fold(merge(seeds), |current, m| {
+            let mut next = Vec::with_capacity(current.len());
+            for r in current {
+                m.apply_interval(r, &mut next);
             }
+            merge(next)
+        })
+    }
+
+    /**
+     * The lowest location reached from any seed in `seeds`, which fails when
+     * there are no seeds to start from
+     */
+    fn lowest_location(&self, seeds: Vec<Range<u64>>) -> anyhow::Result<u64> {
+        match self.apply_intervals(seeds).first() {
+            Some(locations) => Ok(locations.start),
+            None => anyhow::bail!("no seeds, so no lowest location"),
         }
-        v
     }
 }
 
-fn build_maps(input: &str) -> (Vec<u64>, MapSeq) {
-    let seeds_re = Regex::new(r"seeds: (.+)").unwrap();
-    let map_header_re = Regex::new(r"(\w+)-to-(\w+) map:").unwrap();
-    let mut seeds: Option<Vec<u64>> = None;
-    let mut lines = input.lines().map(str::trim);
-    let mut maps: HashMap<String, Map> = Default::default();
-
-    loop {
-        let Some(line) = lines.next() else {
-            break;
+/**
+ * The seeds, plus every map in the almanac as an edge from its source
+ * category to its destination category, whatever order they were listed in
+ */
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Almanac {
+    seeds: Vec<u64>,
+    graph: DiGraph<String, Map>,
+    categories: HashMap<String, NodeIndex>,
+}
+
+impl Almanac {
+    fn parse(input: &str) -> anyhow::Result<Self> {
+        let seeds_re = Regex::new(r"seeds: (.+)").unwrap();
+        let map_header_re = Regex::new(r"(\w+)-to-(\w+) map:").unwrap();
+        let mut almanac = Almanac {
+            seeds: vec![],
+            graph: DiGraph::new(),
+            categories: HashMap::new(),
         };
+        let mut seeds: Option<Vec<u64>> = None;
+        let mut lines = input.lines().map(str::trim);
 
-        if line.len() == 0 {
-            continue;
-        }
-        if let Some((_, [seed_str])) = seeds_re.captures(line).map(|c| c.extract()) {
-            seeds.replace(
-                seed_str
-                    .split_whitespace()
-                    .map(|s| s.parse().unwrap())
-                    .collect(),
-            );
-        } else if let Some((_, [source_str, dest_str])) =
-            map_header_re.captures(line).map(|c| c.extract())
-        {
-            let mut map = Map::new();
-            loop {
-                let Some(line) = lines.next() else {
-                    break;
-                };
-                if line.trim().len() == 0 {
-                    break;
+        while let Some(line) = lines.next() {
+            if line.is_empty() {
+                continue;
+            }
+            if let Some((_, [seed_str])) = seeds_re.captures(line).map(|c| c.extract()) {
+                seeds.replace(
+                    seed_str
+                        .split_whitespace()
+                        .map(str::parse)
+                        .collect::<Result<_, _>>()?,
+                );
+            } else if let Some((_, [source_str, dest_str])) =
+                map_header_re.captures(line).map(|c| c.extract())
+            {
+                let mut map = Map::new();
+                for line in lines.by_ref().take_while(|l| !l.is_empty()) {
+                    let numbers: Vec<u64> = line
+                        .split_whitespace()
+                        .map(str::parse)
+                        .collect::<Result<_, _>>()?;
+                    if numbers.len() != 3 {
+                        anyhow::bail!("expected three numbers in map line: {}", line);
+                    }
+                    map.add_range(numbers.into_iter());
                 }
-                map.add_range(line.split_whitespace().map(|s| s.parse().unwrap()));
+                let (source, dest) = (almanac.category(source_str), almanac.category(dest_str));
+                if almanac.graph.find_edge(source, dest).is_some() {
+                    anyhow::bail!("more than one {}-to-{} map", source_str, dest_str);
+                }
+                almanac.graph.add_edge(source, dest, map);
+            } else {
+                anyhow::bail!("unexpected line: {}", line);
             }
-            maps.insert(source_str.to_string(), map);
         }
+        almanac.seeds = seeds.ok_or_else(|| anyhow::anyhow!("no seeds line"))?;
+        Ok(almanac)
+    }
+
+    fn category(&mut self, name: &str) -> NodeIndex {
+        if let Some(&idx) = self.categories.get(name) {
+            return idx;
+        }
+        let idx = self.graph.add_node(name.to_string());
+        self.categories.insert(name.to_string(), idx);
+        idx
     }
-    let seeds = seeds.unwrap();
-    let order = [
-        "seed",
-        "soil",
-        "fertilizer",
-        "water",
-        "light",
-        "temperature",
-        "humidity",
-    ];
 
-    let map_layers = MapSeq::new(order.iter().map(|s| maps.remove(*s).unwrap()).collect());
-    (seeds, map_layers)
+    /**
+     * The maps that take `from` numbers to `to` numbers, in order. Fails if
+     * there is no way to get there, or more than one.
+     */
+    fn chain(&self, from: &str, to: &str) -> anyhow::Result<MapSeq> {
+        let lookup = |name: &str| {
+            self.categories
+                .get(name)
+                .copied()
+                .ok_or_else(|| anyhow::anyhow!("no maps to or from {}", name))
+        };
+        let (start, end) = (lookup(from)?, lookup(to)?);
+        if start == end {
+            return Ok(MapSeq::new(vec![]));
+        }
+
+        let paths: Vec<Vec<NodeIndex>> =
+            all_simple_paths(&self.graph, start, end, 0, None).collect();
+        let path = match &paths[..] {
+            [] => anyhow::bail!("no chain of maps from {} to {}", from, to),
+            [path] => path,
+            _ => anyhow::bail!(
+                "more than one chain of maps from {} to {}: {}",
+                from,
+                to,
+                paths
+                    .iter()
+                    .map(|p| p.iter().map(|&n| self.graph[n].as_str()).join(" -> "))
+                    .join(", ")
+            ),
+        };
+        let maps = path
+            .windows(2)
+            .map(|pair| {
+                let edge = self.graph.find_edge(pair[0], pair[1]).unwrap();
+                self.graph[edge].clone()
+            })
+            .collect();
+        Ok(MapSeq::new(maps))
+    }
+}
+
+fn build_maps(input: &str) -> (Vec<u64>, MapSeq) {
+    let almanac = Almanac::parse(input).unwrap();
+    let map_layers = almanac.chain("seed", "location").unwrap();
+    (almanac.seeds, map_layers)
 }
+
+/**
+ * Print the seed-to-location maps composed into one table, then which
+ * numbers land on the lowest location any listed seed reaches
+ */
+pub(crate) fn print_table() -> anyhow::Result<()> {
+    let (seeds, map_layers) = build_maps(read_file("input/day5.txt")?);
+    let f = map_layers.compose();
+    print!("{}", f);
+    if let Some(lowest) = seeds.iter().map(|s| f.apply(*s)).min() {
+        println!(
+            "location {} is reached from {:?}",
+            lowest,
+            f.inverse(lowest)
+        );
+    }
+    Ok(())
+}
+
 struct Part1;
 
 impl PuzzleRun for Part1 {
@@ -149,10 +441,8 @@ impl PuzzleRun for Part1 {
 
     fn run(&self, input: &str) -> String {
         let (seeds, map_layers) = build_maps(input);
-        format!(
-            "{}",
-            seeds.iter().map(|s| map_layers.apply(*s)).min().unwrap()
-        )
+        let f = map_layers.compose();
+        format!("{}", seeds.iter().map(|s| f.apply(*s)).min().unwrap())
     }
 }
 
@@ -197,30 +487,24 @@ impl PuzzleRun for Part2 {
         */
     }
 
+    #[cfg(feature = "serde")]
+    fn dump_parsed(
+        &self,
+        input: &str,
+        out: &mut dyn std::io::Write,
+        format: DumpFormat,
+    ) -> anyhow::Result<()> {
+        write_model(&Almanac::parse(input)?, out, format)
+    }
+
     fn run(&self, input: &str) -> String {
         let (seeds, map_layers) = build_maps(input);
-        let mut lowest_location = u64::MAX;
-
-        for (start, len) in seeds.iter().tuples() {
-            println!("starting range from {} len {}", start, len);
-            let this_min = (*start..(start + len))
-                .into_par_iter()
-                .map(|s| map_layers.apply(s))
-                .min()
-                .unwrap();
-            if this_min < lowest_location {
-                lowest_location = this_min;
-            }
-            /*
-            for maybe_min in map_layers.maybe_mins(*start, *len) {
-                let l = map_layers.apply(maybe_min);
-                if l < lowest_location {
-                    lowest_location = l;
-                }
-            }
-            */
-        }
-        format!("{}", lowest_location)
+        let seeds = seeds
+            .iter()
+            .tuples()
+            .map(|(&start, &len)| start..start + len)
+            .collect();
+        format!("{}", map_layers.lowest_location(seeds).unwrap())
     }
 }
 
@@ -229,6 +513,66 @@ mod test {
 
     use super::*;
 
+    const EXAMPLE: &str = "seeds: 79 14 55 13
+
+seed-to-soil map:
+50 98 2
+52 50 48
+
+soil-to-fertilizer map:
+0 15 37
+37 52 2
+39 0 15
+
+fertilizer-to-water map:
+49 53 8
+0 11 42
+42 0 7
+57 7 4
+
+water-to-light map:
+88 18 7
+18 25 70
+
+light-to-temperature map:
+45 77 23
+81 45 19
+68 64 13
+
+temperature-to-humidity map:
+0 69 1
+1 0 69
+
+humidity-to-location map:
+60 56 37
+56 93 4";
+
+    /**
+     * Small maps with non-overlapping source ranges, from a fixed seed
+     */
+    fn random_maps(seed: u64) -> MapSeq {
+        let mut state = seed;
+        let mut next = |n: u64| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 33) % n
+        };
+        let maps = (0..4)
+            .map(|_| {
+                let mut map = Map::new();
+                let mut src = next(5);
+                while src < 80 {
+                    let len = 1 + next(12);
+                    map.add_range([next(100), src, len].into_iter());
+                    src += len + next(6);
+                }
+                map
+            })
+            .collect();
+        MapSeq::new(maps)
+    }
+
     #[test]
     fn test_part1() {
         println!("{}", Part1.run(Part1.input_data().unwrap()));
@@ -238,4 +582,156 @@ mod test {
     fn test_part2() {
         println!("{}", Part2.run(Part2.input_data().unwrap()));
     }
+
+    #[test]
+    fn test_example() {
+        assert_eq!(Part1.run(EXAMPLE), "35");
+        assert_eq!(Part2.run(EXAMPLE), "46");
+    }
+
+    #[test]
+    fn test_no_seeds() {
+        let (_, maps) = build_maps(EXAMPLE);
+        assert!(maps.lowest_location(vec![]).is_err());
+        assert!(maps.lowest_location(vec![5..5, 9..9]).is_err());
+        assert_eq!(maps.lowest_location(vec![79..93, 55..68]).unwrap(), 46);
+    }
+
+    #[test]
+    fn test_merge() {
+        assert_eq!(merge(vec![5..7, 0..2, 2..3, 6..9, 4..4]), vec![0..3, 5..9]);
+    }
+
+    #[test]
+    fn test_intervals_match_brute_force() {
+        for seed in 0..200 {
+            let maps = random_maps(seed);
+            let seeds = vec![seed % 7..seed % 7 + 20, 30..31, 50..95];
+
+            let mut expected: Vec<u64> = seeds
+                .iter()
+                .flat_map(|r| r.clone().map(|n| maps.apply(n)))
+                .collect();
+            expected.sort_unstable();
+            expected.dedup();
+
+            let found: Vec<u64> = maps.apply_intervals(seeds).into_iter().flatten().collect();
+            assert_eq!(found, expected, "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn test_compose() {
+        let (seeds, maps) = build_maps(EXAMPLE);
+        let f = maps.compose();
+        assert_eq!(f.pieces[0].src_base, 0);
+        for pair in f.pieces.windows(2) {
+            assert_eq!(pair[0].src().end, pair[1].src_base);
+        }
+        assert_eq!(f.pieces.last().unwrap().src().end, u64::MAX);
+
+        for n in (0..200).chain(seeds) {
+            assert_eq!(f.apply(n), maps.apply(n), "{}", n);
+        }
+        for seed in 0..40 {
+            let maps = random_maps(seed);
+            let f = maps.compose();
+            for n in 0..120 {
+                assert_eq!(f.apply(n), maps.apply(n));
+            }
+        }
+    }
+
+    #[test]
+    fn test_inverse() {
+        let (_, maps) = build_maps(EXAMPLE);
+        let f = maps.compose();
+        assert_eq!(f.apply(79), 82);
+        assert!(f.inverse(82).contains(&79));
+        for location in 0..120 {
+            let seeds = f.inverse(location);
+            let expected: Vec<u64> = (0..200).filter(|&n| maps.apply(n) == location).collect();
+            assert!(seeds.iter().all(|&n| maps.apply(n) == location));
+            assert_eq!(
+                seeds
+                    .iter()
+                    .copied()
+                    .filter(|&n| n < 200)
+                    .collect::<Vec<_>>(),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_table() {
+        let mut map = Map::new();
+        map.add_range([52, 50, 48].into_iter());
+        map.add_range([50, 98, 2].into_iter());
+        let table = map.to_piecewise().to_string();
+        let rows: Vec<Vec<&str>> = table
+            .lines()
+            .map(|l| l.split_whitespace().collect())
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                vec!["from", "to", "shift"],
+                vec!["0..50", "0..50", "+0"],
+                vec!["50..98", "52..100", "+2"],
+                vec!["98..100", "50..52", "-48"],
+                vec![
+                    "100..18446744073709551615",
+                    "100..18446744073709551615",
+                    "+0"
+                ],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_chains() {
+        // the same almanac with its maps listed backwards
+        let mut blocks: Vec<&str> = EXAMPLE.split("\n\n").collect();
+        blocks[1..].reverse();
+        let almanac = Almanac::parse(&blocks.join("\n\n")).unwrap();
+        let (seeds, maps) = build_maps(EXAMPLE);
+        assert_eq!(almanac.seeds, seeds);
+        let chain = almanac.chain("seed", "location").unwrap();
+        for n in 0..200 {
+            assert_eq!(chain.apply(n), maps.apply(n));
+        }
+
+        // seed 79 has soil 81, fertilizer 81, water 81, light 74 and
+        // humidity 78
+        assert_eq!(almanac.chain("soil", "light").unwrap().apply(81), 74);
+        assert_eq!(almanac.chain("seed", "humidity").unwrap().apply(79), 78);
+        assert_eq!(almanac.chain("water", "water").unwrap().apply(5), 5);
+
+        let err = almanac.chain("location", "seed").unwrap_err();
+        assert_eq!(err.to_string(), "no chain of maps from location to seed");
+        assert!(almanac.chain("seed", "colour").is_err());
+    }
+
+    #[test]
+    fn test_bad_links() {
+        let input = "seeds: 1 2
+
+a-to-b map:
+10 0 5
+
+b-to-c map:
+0 10 1
+
+a-to-c map:
+7 0 1";
+        let almanac = Almanac::parse(input).unwrap();
+        assert_eq!(almanac.chain("b", "c").unwrap().apply(10), 0);
+        let err = almanac.chain("a", "c").unwrap_err().to_string();
+        assert!(err.starts_with("more than one chain of maps from a to c: "));
+        assert!(err.contains("a -> b -> c"));
+
+        let twice = format!("{}\n\nb-to-c map:\n1 2 3", input);
+        assert!(Almanac::parse(&twice).is_err());
+    }
 }
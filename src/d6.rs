@@ -1,60 +1,123 @@
 use crate::{read_file, PuzzleRun};
-use num::integer::sqrt;
-use regex::Regex;
+use num::{
+    integer::Roots,
+    traits::{CheckedMul, CheckedSub},
+    Integer,
+};
+use std::{ops::RangeInclusive, str::FromStr};
 
 pub(crate) fn get_runs() -> Vec<Box<dyn PuzzleRun>> {
     vec![Box::new(Part1)]
 }
 
-#[derive(Debug)]
-struct RaceData {
-    time: u32,
-    record: u32,
+/**
+ * How to read the numbers on each line: as separate races, or with the
+ * spaces between them ignored so the line is a single number
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kerning {
+    Spaced,
+    Joined,
 }
 
-impl RaceData {
-    fn new(time: u32, record: u32) -> Self {
+#[derive(Debug, PartialEq, Eq)]
+struct RaceData<N> {
+    time: N,
+    record: N,
+}
+
+impl<N> RaceData<N> {
+    fn new(time: N, record: N) -> Self {
         Self { time, record }
     }
 }
 
-fn parse_race_data(input: &str) -> Vec<RaceData> {
-    let re = Regex::new(r"(Time:|Distance:)([\s\d]+)").unwrap();
-    let mut lines = input.lines();
-    let times: Vec<u32> = match re.captures(lines.next().unwrap()).map(|c| c.extract()) {
-        Some((_, [t_or_d, nums])) if t_or_d == "Time:" => nums
-            .trim()
-            .split_whitespace()
-            .map(|s| s.parse().unwrap())
-            .collect(),
-        _ => panic!(),
+impl<N> RaceData<N>
+where
+    N: Integer + Roots + Clone + CheckedMul + CheckedSub + From<u8>,
+{
+    fn distance(&self, hold: &N) -> anyhow::Result<N> {
+        hold.checked_mul(&(self.time.clone() - hold.clone()))
+            .ok_or_else(|| anyhow::anyhow!("distance overflows"))
+    }
+
+    /**
+     * Every hold time that beats the record, as `lo..=hi`. Holding for `p`
+     * goes `p * (time - p)`, so the winners are the integers strictly between
+     * the roots of `p^2 - time * p + record`, and the range is symmetric
+     * about `time / 2`.
+     */
+    fn winning_holds(&self) -> anyhow::Result<Option<RangeInclusive<N>>> {
+        let overflow = || anyhow::anyhow!("time squared overflows");
+        let (two, four) = (N::from(2), N::from(4));
+        let square = self.time.checked_mul(&self.time).ok_or_else(overflow)?;
+        let four_record = self.record.checked_mul(&four).ok_or_else(overflow)?;
+        if four_record >= square {
+            return Ok(None);
+        }
+        let root = (square - four_record).sqrt();
+
+        // the integer square root can put this up to one below the first
+        // winner, but never above it
+        let mut lo = (self.time.clone() - root) / two;
+        while lo <= self.time && self.distance(&lo)? <= self.record {
+            lo = lo + N::one();
+        }
+        if lo > self.time {
+            return Ok(None);
+        }
+        let hi = self.time.clone() - lo.clone();
+        if lo > hi {
+            return Ok(None);
+        }
+        Ok(Some(lo..=hi))
+    }
+
+    fn ways_to_win(&self) -> anyhow::Result<N> {
+        Ok(match self.winning_holds()? {
+            Some(holds) => {
+                let (lo, hi) = holds.into_inner();
+                hi - lo + N::one()
+            }
+            None => N::zero(),
+        })
+    }
+}
+
+fn parse_numbers<N: FromStr>(line: &str, label: &str, kerning: Kerning) -> anyhow::Result<Vec<N>>
+where
+    N::Err: std::error::Error + Send + Sync + 'static,
+{
+    let Some(nums) = line.trim().strip_prefix(label) else {
+        anyhow::bail!("expected {} line, got: {}", label, line);
     };
-    let dists: Vec<u32> = match re.captures(lines.next().unwrap()).map(|c| c.extract()) {
-        Some((_, [t_or_d, nums])) if t_or_d == "Distance:" => nums
-            .trim()
+    let parsed = match kerning {
+        Kerning::Spaced => nums
             .split_whitespace()
-            .map(|s| s.parse().unwrap())
-            .collect(),
-        _ => panic!(),
+            .map(str::parse)
+            .collect::<Result<_, _>>()?,
+        Kerning::Joined => vec![nums.split_whitespace().collect::<String>().parse()?],
     };
-    assert_eq!(times.len(), dists.len());
-    times
-        .into_iter()
-        .zip(dists.into_iter())
-        .map(|(t, d)| RaceData::new(t, d))
-        .collect()
+    Ok(parsed)
 }
 
-fn winners(time: u32, record: u32) -> Vec<u32> {
-    let mut v = vec![];
-    for p in 0..time {
-        let d = p * (time - p);
-        if d > record {
-            v.push(p)
-        }
+fn parse_race_data<N: FromStr>(input: &str, kerning: Kerning) -> anyhow::Result<Vec<RaceData<N>>>
+where
+    N::Err: std::error::Error + Send + Sync + 'static,
+{
+    let mut lines = input.lines();
+    let times = parse_numbers(lines.next().unwrap_or_default(), "Time:", kerning)?;
+    let records = parse_numbers(lines.next().unwrap_or_default(), "Distance:", kerning)?;
+    if times.len() != records.len() {
+        anyhow::bail!("{} times but {} distances", times.len(), records.len());
     }
-    v
+    Ok(times
+        .into_iter()
+        .zip(records)
+        .map(|(time, record)| RaceData::new(time, record))
+        .collect())
 }
+
 struct Part1;
 
 impl PuzzleRun for Part1 {
@@ -67,14 +130,13 @@ impl PuzzleRun for Part1 {
     }
 
     fn run(&self, input: &str) -> String {
-        let races = parse_race_data(input);
+        let races = parse_race_data::<u64>(input, Kerning::Spaced).unwrap();
         format!(
             "{}",
             races
                 .iter()
-                .map(|r| winners(r.time, r.record).len())
-                .reduce(|acc, v| acc * v)
-                .unwrap()
+                .map(|r| r.ways_to_win().unwrap())
+                .product::<u64>()
         )
     }
 }
@@ -91,30 +153,8 @@ impl PuzzleRun for Part2 {
     }
 
     fn run(&self, input: &str) -> String {
-        let mut lines = input.lines();
-        let line = lines.next().unwrap();
-        let mut buf: Vec<char> = Default::default();
-        for c in line.chars() {
-            if c.is_ascii_digit() {
-                buf.push(c);
-            }
-        }
-        let time: u32 = buf.into_iter().collect::<String>().parse().unwrap();
-
-        let line = lines.next().unwrap();
-        let mut buf: Vec<char> = Default::default();
-        for c in line.chars() {
-            if c.is_ascii_digit() {
-                buf.push(c);
-            }
-        }
-        let record: u64 = buf.into_iter().collect::<String>().parse().unwrap();
-
-        let D = sqrt(time as u64 * time as u64 - 4 * record as u64);
-        let max = (time as u64 + D) / 2;
-        let min = (time as u64 - D) / 2;
-
-        format!("{}", max - min)
+        let races = parse_race_data::<u64>(input, Kerning::Joined).unwrap();
+        format!("{}", races[0].ways_to_win().unwrap())
     }
 }
 
@@ -122,14 +162,32 @@ impl PuzzleRun for Part2 {
 mod test {
 
     use super::*;
+    use num::BigInt;
+
+    const EXAMPLE: &str = "Time:      7  15   30
+        Distance:  9  40  200";
+
+    fn brute_force(time: u64, record: u64) -> Vec<u64> {
+        (0..=time).filter(|p| p * (time - p) > record).collect()
+    }
 
     #[test]
     fn test_parse() {
-        let input = Part1.input_data().unwrap();
-        let re = Regex::new(r"(Time:|Distance:)([\s\d]+)").unwrap();
-        let rd = parse_race_data(input);
+        let rd = parse_race_data::<u32>(EXAMPLE, Kerning::Spaced).unwrap();
         assert_eq!(rd.len(), 3);
-        println!("{:?}", rd);
+        assert_eq!(rd[2], RaceData::new(30, 200));
+
+        let rd = parse_race_data::<u64>(EXAMPLE, Kerning::Joined).unwrap();
+        assert_eq!(rd, vec![RaceData::new(71530, 940200)]);
+
+        assert!(parse_race_data::<u32>("Time: 1 2\nDistance: 3", Kerning::Spaced).is_err());
+        assert!(parse_race_data::<u32>("Distance: 1\nTime: 3", Kerning::Spaced).is_err());
+    }
+
+    #[test]
+    fn test_example() {
+        assert_eq!(Part1.run(EXAMPLE), "288");
+        assert_eq!(Part2.run(EXAMPLE), "71503");
     }
 
     #[test]
@@ -141,4 +199,43 @@ mod test {
     fn test_part2() {
         println!("{}", Part2.run(Part2.input_data().unwrap()));
     }
+
+    #[test]
+    fn test_matches_brute_force() {
+        for time in 0..60u64 {
+            for record in 0..time * time / 4 + 2 {
+                let expected = brute_force(time, record);
+                let holds = RaceData::new(time, record).winning_holds().unwrap();
+                assert_eq!(
+                    holds.map(|h| h.collect::<Vec<_>>()).unwrap_or_default(),
+                    expected,
+                    "time {} record {}",
+                    time,
+                    record
+                );
+
+                let wide = RaceData::new(time as u128, record as u128);
+                assert_eq!(wide.ways_to_win().unwrap(), expected.len() as u128);
+                let big = RaceData::new(BigInt::from(time), BigInt::from(record));
+                assert_eq!(big.ways_to_win().unwrap(), BigInt::from(expected.len()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_big() {
+        assert!(RaceData::new(u64::MAX, 1).winning_holds().is_err());
+        let wide = RaceData::new(u64::MAX as u128, 1);
+        assert_eq!(wide.ways_to_win().unwrap(), u64::MAX as u128 - 1);
+
+        let time: BigInt = "1000000000000000000000000000001".parse().unwrap();
+        let record: BigInt = "123456789012345678901234567890123456789".parse().unwrap();
+        let race = RaceData::new(time.clone(), record.clone());
+        let (lo, hi) = race.winning_holds().unwrap().unwrap().into_inner();
+        let one = BigInt::from(1);
+        assert!(race.distance(&lo).unwrap() > record);
+        assert!(race.distance(&(lo.clone() - &one)).unwrap() <= record);
+        assert_eq!(hi.clone() + &lo, time);
+        assert_eq!(race.ways_to_win().unwrap(), hi - lo + one);
+    }
 }
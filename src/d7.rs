@@ -1,19 +1,15 @@
 use crate::{read_file, PuzzleRun};
-use itertools::Itertools;
-use std::cmp::Ordering;
+use std::collections::HashMap;
 
 pub(crate) fn get_runs() -> Vec<Box<dyn PuzzleRun>> {
     vec![Box::new(Part1)]
 }
 
-trait Part {
-    fn score(hand: &[u8]) -> (Score, Vec<u8>);
-    fn hands_eq(this: &Hand<Self>, that: &Hand<Self>) -> bool;
-    fn compare_hands(this: &Hand<Self>, that: &Hand<Self>) -> Option<Ordering>;
-    fn cmp_card(s: u8, o: u8) -> Ordering;
-}
-
-#[derive(Debug, PartialOrd, PartialEq)]
+/**
+ * The usual names for a hand's category. Only a label: hands are ranked by
+ * their full `shape`, which also covers hands of other sizes.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 enum Score {
     High,
     Pair,
@@ -23,152 +19,137 @@ enum Score {
     Four,
     Five,
 }
-#[derive(Debug)]
-struct Hand<P: ?Sized> {
-    cards: [u8; 5],
-    bid: u32,
-    score: Score,
-    sorted_cards: Vec<u8>,
-    phantom: std::marker::PhantomData<P>,
-}
 
-impl<P: Part> Hand<P> {
-    fn new(cards: [u8; 5], bid: u32, part: P) -> Self {
-        let (score, sorted_cards) = P::score(&cards);
-        Self {
-            cards,
-            bid,
-            score,
-            sorted_cards,
-            phantom: std::marker::PhantomData,
+impl Score {
+    fn of(shape: &[usize]) -> Self {
+        match shape {
+            [n, ..] if *n >= 5 => Score::Five,
+            [4, ..] => Score::Four,
+            [3, n, ..] if *n >= 2 => Score::Full,
+            [3, ..] => Score::Three,
+            [2, 2, ..] => Score::TwoPair,
+            [2, ..] => Score::Pair,
+            _ => Score::High,
         }
     }
 }
 
-// cards must be sorted
-fn has_seq(cards: &[u8], len: usize, start: usize) -> Option<usize> {
-    for i in start..cards.len() - len + 1 {
-        if cards[i..(i + len)].iter().all_equal() {
-            return Some(i + len);
-        }
-    }
-    None
+/**
+ * How to rank hands: the cards from weakest to strongest, which of them are
+ * wild, and how many cards make a hand
+ */
+#[derive(Debug, Clone)]
+struct Rules {
+    order: Vec<u8>,
+    wild: Vec<u8>,
+    hand_size: usize,
 }
 
-impl Part for Part1 {
-    // must be sorted
-    fn score(cards: &[u8]) -> (Score, Vec<u8>) {
-        let mut sorted: Vec<u8> = Default::default();
-        cards.clone_into(&mut sorted);
-        sorted.sort_by(|&s, &o| Self::cmp_card(s, o));
-
-        if has_seq(&sorted, 5, 0).is_some() {
-            (Score::Five, sorted)
-        } else if has_seq(&sorted, 4, 0).is_some() {
-            (Score::Four, sorted)
-        } else if let Some(i) = has_seq(&sorted, 3, 0) {
-            if i == 3 && has_seq(&sorted, 2, 3).is_some() {
-                (Score::Full, sorted)
-            } else if i == 5 && sorted[0] == sorted[1] {
-                (Score::Full, sorted)
-            } else {
-                (Score::Three, sorted)
-            }
-        } else if let Some(i) = has_seq(&sorted, 2, 0) {
-            if has_seq(&sorted, 2, i).is_some() {
-                (Score::TwoPair, sorted)
-            } else {
-                (Score::Pair, sorted)
-            }
-        } else {
-            (Score::High, sorted)
+impl Rules {
+    fn new(order: &str, wild: &str, hand_size: usize) -> anyhow::Result<Self> {
+        if let Some(c) = wild.bytes().find(|c| !order.as_bytes().contains(c)) {
+            anyhow::bail!("wild card {} is not in the card order", c as char);
         }
+        Ok(Self {
+            order: order.bytes().collect(),
+            wild: wild.bytes().collect(),
+            hand_size,
+        })
     }
 
-    fn hands_eq(this: &Hand<Self>, that: &Hand<Self>) -> bool {
-        this.sorted_cards == that.sorted_cards
+    fn part1() -> Self {
+        Self::new("23456789TJQKA", "", 5).unwrap()
     }
 
-    fn compare_hands(this: &Hand<Self>, that: &Hand<Self>) -> Option<Ordering> {
-        let mut this_sorted = this.cards.clone();
-        this_sorted.sort();
-        let mut that_sorted = that.cards.clone();
-        that_sorted.sort();
-
-        if this_sorted == that_sorted {
-            Some(Ordering::Equal)
-        } else {
-            match this.score.partial_cmp(&that.score) {
-                Some(Ordering::Equal) => {
-                    for (s, o) in this.cards.iter().zip(that.cards.iter()) {
-                        match Self::cmp_card(*s, *o) {
-                            Ordering::Equal => continue,
-                            ord => return Some(ord),
-                        }
-                    }
-                    panic!()
-                }
-                ord => return ord,
+    /**
+     * Jacks are jokers: wild, and the weakest card when breaking ties
+     */
+    fn part2() -> Self {
+        Self::new("J23456789TQKA", "J", 5).unwrap()
+    }
+
+    fn rank(&self, card: u8) -> Option<usize> {
+        self.order.iter().position(|&c| c == card)
+    }
+
+    /**
+     * How many cards of each rank the hand holds, largest first, with the
+     * wild cards joining the largest group since that always gives the best
+     * hand. Comparing shapes in order ranks the categories: five of a kind
+     * is `[5]`, a full house `[3, 2]`, two pair `[2, 2, 1]` and so on.
+     */
+    fn shape(&self, cards: &[u8]) -> Vec<usize> {
+        let mut counts: HashMap<u8, usize> = HashMap::new();
+        let mut wild = 0;
+        for &c in cards {
+            if self.wild.contains(&c) {
+                wild += 1;
+            } else {
+                *counts.entry(c).or_default() += 1;
             }
         }
+        let mut shape: Vec<usize> = counts.into_values().collect();
+        shape.sort_unstable_by(|a, b| b.cmp(a));
+        match shape.first_mut() {
+            Some(most) => *most += wild,
+            None => shape.push(wild),
+        }
+        shape
     }
 
-    fn cmp_card(s: u8, o: u8) -> Ordering {
-        if s == o {
-            Ordering::Equal
-        } else if s == b'A' {
-            Ordering::Greater
-        } else if o == b'A' {
-            Ordering::Less
-        } else if s == b'K' {
-            Ordering::Greater
-        } else if o == b'K' {
-            Ordering::Less
-        } else if s == b'Q' {
-            Ordering::Greater
-        } else if o == b'Q' {
-            Ordering::Less
-        } else if s == b'J' {
-            Ordering::Greater
-        } else if o == b'J' {
-            Ordering::Less
-        } else if s == b'T' {
-            Ordering::Greater
-        } else if o == b'T' {
-            Ordering::Less
-        } else {
-            s.cmp(&o)
+    fn parse_hand(&self, input: &str) -> anyhow::Result<Hand> {
+        let Some((cards, bid)) = input.trim().split_once(' ') else {
+            anyhow::bail!("expected cards and a bid: {}", input);
+        };
+        let cards = cards.as_bytes();
+        if cards.len() != self.hand_size {
+            anyhow::bail!("expected {} cards: {}", self.hand_size, input);
         }
+        let ranks = cards
+            .iter()
+            .map(|&c| {
+                self.rank(c)
+                    .ok_or_else(|| anyhow::anyhow!("unknown card {}", c as char))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Hand {
+            cards: cards.to_vec(),
+            bid: bid.trim().parse()?,
+            shape: self.shape(cards),
+            ranks,
+        })
     }
 }
 
-impl<P: Part> Eq for Hand<P> {}
-
-impl<P: Part> PartialEq for Hand<P> {
-    fn eq(&self, other: &Self) -> bool {
-        P::hands_eq(self, other)
-    }
+/**
+ * A hand ranked under some `Rules`. Hands order by shape, then card by card
+ * in the order they were dealt.
+ */
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Hand {
+    shape: Vec<usize>,
+    ranks: Vec<usize>,
+    cards: Vec<u8>,
+    bid: u32,
 }
 
-impl<P: Part> Ord for Hand<P> {
-    fn cmp(&self, other: &Self) -> Ordering {
-        self.partial_cmp(other).unwrap()
+impl Hand {
+    fn score(&self) -> Score {
+        Score::of(&self.shape)
     }
 }
 
-impl<P: Part> PartialOrd for Hand<P> {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        P::compare_hands(self, other)
-    }
+fn parse_hand(input: &str, rules: &Rules) -> Hand {
+    rules.parse_hand(input).unwrap()
 }
 
-fn parse_hand<P: Part>(input: &str, part: P) -> Hand<P> {
-    let input = input.trim();
-    Hand::new(
-        input.as_bytes()[0..5].try_into().unwrap(),
-        input[6..].parse().unwrap(),
-        part,
-    )
+fn winnings(input: &str, rules: &Rules) -> usize {
+    let mut hands: Vec<Hand> = input.lines().map(|s| parse_hand(s, rules)).collect();
+    hands.sort();
+    hands
+        .into_iter()
+        .enumerate()
+        .fold(0, |acc, (i, hand)| acc + (i + 1) * hand.bid as usize)
 }
 
 struct Part1;
@@ -186,137 +167,13 @@ impl PuzzleRun for Part1 {
     }
 
     fn run(&self, input: &str) -> String {
-        let mut hands: Vec<Hand<_>> = input.lines().map(|s| parse_hand(s, Self)).collect();
-        hands.sort();
-
-        /*
-        for (i, h) in hands.iter().enumerate() {
-            println!(
-                "{} ({:?}):\t{} [{}] [bid: {}] [score: {}]",
-                i + 1,
-                h.score,
-                std::str::from_utf8(&h.sorted_cards).unwrap(),
-                std::str::from_utf8(&h.cards).unwrap(),
-                h.bid,
-                h.bid as usize * (i + 1),
-            );
-        }
-        */
-        format!(
-            "{}",
-            hands
-                .into_iter()
-                .enumerate()
-                //                .inspect(|v| println!("{:?}", v))
-                .fold(0, |acc, (i, hand)| acc + (i + 1) * hand.bid as usize)
-        )
+        format!("{}", winnings(input, &Rules::part1()))
     }
 }
 
 #[derive(Debug)]
 struct Part2;
 
-impl Part for Part2 {
-    // must be sorted
-    fn score(cards: &[u8]) -> (Score, Vec<u8>) {
-        let mut sorted: Vec<u8> = cards.iter().filter(|&c| *c != b'J').copied().collect();
-        sorted.sort_by(|&s, &o| Self::cmp_card(s, o));
-        let sorted = sorted; // no further mutability
-
-        if sorted.len() == 5 {
-            Part1::score(cards)
-        } else if sorted.len() == 4 {
-            if has_seq(&sorted, 4, 0).is_some() {
-                (Score::Five, sorted)
-            } else if has_seq(&sorted, 3, 0).is_some() {
-                (Score::Four, sorted)
-            } else if let Some(i) = has_seq(&sorted, 2, 0) {
-                if has_seq(&sorted, 2, i).is_some() {
-                    (Score::Full, sorted)
-                } else {
-                    (Score::Three, sorted)
-                }
-            } else {
-                (Score::Pair, sorted)
-            }
-        } else if sorted.len() == 3 {
-            // two wild cards
-            if has_seq(&sorted, 3, 0).is_some() {
-                (Score::Five, sorted)
-            } else if has_seq(&sorted, 2, 0).is_some() {
-                (Score::Four, sorted)
-            } else {
-                (Score::Three, sorted)
-            }
-        } else if sorted.len() == 2 {
-            // three wild cards
-            if has_seq(&sorted, 2, 0).is_some() {
-                (Score::Five, sorted)
-            } else {
-                (Score::Four, sorted)
-            }
-        } else {
-            (Score::Five, sorted)
-        }
-    }
-
-    fn hands_eq(this: &Hand<Self>, that: &Hand<Self>) -> bool {
-        this.sorted_cards == that.sorted_cards
-    }
-
-    fn compare_hands(this: &Hand<Self>, that: &Hand<Self>) -> Option<Ordering> {
-        let mut this_sorted = this.cards.clone();
-        this_sorted.sort();
-        let mut that_sorted = that.cards.clone();
-        that_sorted.sort();
-
-        if this_sorted == that_sorted {
-            Some(Ordering::Equal)
-        } else {
-            match this.score.partial_cmp(&that.score) {
-                Some(Ordering::Equal) => {
-                    for (s, o) in this.cards.iter().zip(that.cards.iter()) {
-                        match Self::cmp_card(*s, *o) {
-                            Ordering::Equal => continue,
-                            ord => return Some(ord),
-                        }
-                    }
-                    panic!()
-                }
-                ord => return ord,
-            }
-        }
-    }
-
-    fn cmp_card(s: u8, o: u8) -> Ordering {
-        if s == o {
-            Ordering::Equal
-        } else if s == b'A' {
-            Ordering::Greater
-        } else if o == b'A' {
-            Ordering::Less
-        } else if s == b'K' {
-            Ordering::Greater
-        } else if o == b'K' {
-            Ordering::Less
-        } else if s == b'Q' {
-            Ordering::Greater
-        } else if o == b'Q' {
-            Ordering::Less
-        } else if s == b'J' {
-            Ordering::Less
-        } else if o == b'J' {
-            Ordering::Greater
-        } else if s == b'T' {
-            Ordering::Greater
-        } else if o == b'T' {
-            Ordering::Less
-        } else {
-            s.cmp(&o)
-        }
-    }
-}
-
 impl PuzzleRun for Part2 {
     fn input_data(&self) -> anyhow::Result<&str> {
         read_file("input/day7.txt")
@@ -330,17 +187,7 @@ impl PuzzleRun for Part2 {
     }
 
     fn run(&self, input: &str) -> String {
-        let mut hands: Vec<Hand<_>> = input.lines().map(|s| parse_hand(s, Self)).collect();
-        hands.sort();
-
-        format!(
-            "{}",
-            hands
-                .into_iter()
-                .enumerate()
-                //                .inspect(|v| println!("{:?}", v))
-                .fold(0, |acc, (i, hand)| acc + (i + 1) * hand.bid as usize)
-        )
+        format!("{}", winnings(input, &Rules::part2()))
     }
 }
 
@@ -351,92 +198,101 @@ mod test {
 
     #[test]
     fn test_score() {
+        let part1 = Rules::part1();
         let h = "55555 999";
-        let h = parse_hand(h, Part1);
-        assert!(matches!(h.score, Score::Five));
+        let h = parse_hand(h, &part1);
+        assert!(matches!(h.score(), Score::Five));
 
         let h = "55455 999";
-        let h = parse_hand(h, Part1);
-        assert!(matches!(h.score, Score::Four));
+        let h = parse_hand(h, &part1);
+        assert!(matches!(h.score(), Score::Four));
 
         let h = "54455 999";
-        let h = parse_hand(h, Part1);
-        assert!(matches!(h.score, Score::Full));
+        let h = parse_hand(h, &part1);
+        assert!(matches!(h.score(), Score::Full));
 
         let h = "54655 999";
-        let h = parse_hand(h, Part1);
-        assert!(matches!(h.score, Score::Three));
+        let h = parse_hand(h, &part1);
+        assert!(matches!(h.score(), Score::Three));
 
         let h = "5445K 999";
-        let h = parse_hand(h, Part1);
-        assert!(matches!(h.score, Score::TwoPair));
+        let h = parse_hand(h, &part1);
+        assert!(matches!(h.score(), Score::TwoPair));
 
         let h = "544AK 999";
-        let h = parse_hand(h, Part1);
-        assert!(matches!(h.score, Score::Pair));
+        let h = parse_hand(h, &part1);
+        assert!(matches!(h.score(), Score::Pair));
 
         let h = "534AK 999";
-        let h = parse_hand(h, Part1);
-        assert!(matches!(h.score, Score::High));
+        let h = parse_hand(h, &part1);
+        assert!(matches!(h.score(), Score::High));
 
         let h = "24443 999";
-        let h = parse_hand(h, Part1);
-        assert!(matches!(h.score, Score::Three));
+        let h = parse_hand(h, &part1);
+        assert!(matches!(h.score(), Score::Three));
 
         let h = "KK677 999";
-        let h = parse_hand(h, Part1);
-        assert!(matches!(h.score, Score::TwoPair));
+        let h = parse_hand(h, &part1);
+        assert!(matches!(h.score(), Score::TwoPair));
     }
 
     #[test]
     fn test_score_part2() {
+        let part2 = Rules::part2();
         let h = "32T3K 999";
-        let h = parse_hand(h, Part2);
-        assert!(matches!(h.score, Score::Pair));
+        let h = parse_hand(h, &part2);
+        assert!(matches!(h.score(), Score::Pair));
 
         let h = "KK677 999";
-        let h = parse_hand(h, Part2);
-        assert!(matches!(h.score, Score::TwoPair));
+        let h = parse_hand(h, &part2);
+        assert!(matches!(h.score(), Score::TwoPair));
 
         let h = "T55J5 999";
-        let h = parse_hand(h, Part2);
-        assert!(matches!(h.score, Score::Four));
+        let h = parse_hand(h, &part2);
+        assert!(matches!(h.score(), Score::Four));
 
         let h = "KTJJT 999";
-        let h = parse_hand(h, Part2);
-        assert!(matches!(h.score, Score::Four));
+        let h = parse_hand(h, &part2);
+        assert!(matches!(h.score(), Score::Four));
 
         let h = "QQQJA 999";
-        let h = parse_hand(h, Part2);
-        assert!(matches!(h.score, Score::Four));
+        let h = parse_hand(h, &part2);
+        assert!(matches!(h.score(), Score::Four));
+
+        let h = "JJJJJ 999";
+        let h = parse_hand(h, &part2);
+        assert!(matches!(h.score(), Score::Five));
     }
+
     #[test]
     fn test_order() {
+        let part1 = Rules::part1();
         let h1 = "55555 999";
-        let h1 = parse_hand(h1, Part1);
+        let h1 = parse_hand(h1, &part1);
         let h2 = "55554 999";
-        let h2 = parse_hand(h2, Part1);
+        let h2 = parse_hand(h2, &part1);
 
         assert!(h1 > h2);
 
         let h1 = "33332 999";
-        let h1 = parse_hand(h1, Part1);
+        let h1 = parse_hand(h1, &part1);
         let h2 = "2AAAA 999";
-        let h2 = parse_hand(h2, Part1);
+        let h2 = parse_hand(h2, &part1);
 
         assert!(h1 > h2);
 
+        // same category, so the first card decides
         let h1 = "23456 999";
-        let h1 = parse_hand(h1, Part1);
+        let h1 = parse_hand(h1, &part1);
         let h2 = "65432 999";
-        let h2 = parse_hand(h2, Part1);
+        let h2 = parse_hand(h2, &part1);
 
-        assert!(h1 == h2);
+        assert!(h1 < h2);
 
         let h1 = "3A3A3 999";
-        let h1 = parse_hand(h1, Part1);
+        let h1 = parse_hand(h1, &part1);
         let h2 = "AA432 999";
-        let h2 = parse_hand(h2, Part1);
+        let h2 = parse_hand(h2, &part1);
 
         assert!(h1 > h2);
     }
@@ -448,11 +304,70 @@ mod test {
         KK677 28
         KTJJT 220
         QQQJA 483";
-        let mut hands: Vec<Hand<Part2>> = input.lines().map(|s| parse_hand(s, Part2)).collect();
+        let mut hands: Vec<Hand> = input
+            .lines()
+            .map(|s| parse_hand(s, &Rules::part2()))
+            .collect();
         hands.sort();
-        println!("sorted:");
-        for h in hands.iter() {
-            println!("{}", std::str::from_utf8(&h.cards).unwrap());
+        let sorted: Vec<&[u8]> = hands.iter().map(|h| &h.cards[..]).collect();
+        assert_eq!(
+            sorted,
+            vec![b"32T3K", b"KK677", b"T55J5", b"QQQJA", b"KTJJT"]
+        );
+        assert_eq!(winnings(input, &Rules::part2()), 5905);
+    }
+
+    #[test]
+    fn test_variants() {
+        // jokers that are wild but still the strongest card in a tie
+        let high = Rules::new("23456789TQKAJ", "J", 5).unwrap();
+        assert_eq!(parse_hand("JKKK2 1", &high).score(), Score::Four);
+        assert!(parse_hand("JKKK2 1", &high) > parse_hand("QQQQ2 1", &high));
+
+        let two_wild = Rules::new("23456789TJQKA", "J2", 5).unwrap();
+        assert_eq!(parse_hand("2J3A4 1", &two_wild).score(), Score::Three);
+        assert_eq!(parse_hand("2J2JA 1", &two_wild).score(), Score::Five);
+
+        let six = Rules::new("23456789TJQKA", "", 6).unwrap();
+        let shape = |s| parse_hand(s, &six).shape;
+        assert_eq!(shape("AAAKKK 1"), vec![3, 3]);
+        assert!(shape("AAAAK2 1") > shape("AAAKKK 1"));
+        assert!(shape("AAAKKK 1") > shape("AAAKK2 1"));
+        assert!(six.parse_hand("AAAAA 1").is_err());
+
+        assert!(Rules::new("23456789TJQKA", "X", 5).is_err());
+        assert!(Rules::part1().parse_hand("AAAAX 1").is_err());
+    }
+
+    #[test]
+    fn test_wild_never_worse() {
+        let plain = Rules::new("23456789TJQKA", "", 5).unwrap();
+        let wild = Rules::new("23456789TJQKA", "J9", 5).unwrap();
+        let mut state: u64 = 7;
+        for _ in 0..2000 {
+            let cards: Vec<u8> = (0..5)
+                .map(|_| {
+                    state = state
+                        .wrapping_mul(6364136223846793005)
+                        .wrapping_add(1442695040888963407);
+                    plain.order[((state >> 33) % 13) as usize]
+                })
+                .collect();
+            let wild_shape = wild.shape(&cards);
+            assert!(wild_shape >= plain.shape(&cards), "{:?}", cards);
+
+            // and no way of choosing what the wild cards stand for does better
+            let wilds: Vec<usize> = (0..5).filter(|&i| wild.wild.contains(&cards[i])).collect();
+            let choices = 13usize.pow(wilds.len() as u32);
+            for choice in 0..choices {
+                let mut dealt = cards.clone();
+                let mut c = choice;
+                for &i in &wilds {
+                    dealt[i] = plain.order[c % 13];
+                    c /= 13;
+                }
+                assert!(plain.shape(&dealt) <= wild_shape);
+            }
         }
     }
 
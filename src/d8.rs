@@ -1,9 +1,24 @@
-use crate::{read_file, PuzzleRun};
+use crate::{
+    cycle::{crt, Cycle, History},
+    read_file, PuzzleRun,
+};
 use bumpalo::{collections::Vec, Bump};
-use num::integer::lcm;
+use lazy_static::lazy_static;
+use num::integer::gcd;
+use petgraph::{
+    algo::tarjan_scc,
+    dot::Dot,
+    graph::{DiGraph, NodeIndex},
+    visit::{Dfs, NodeFiltered},
+};
 
 use regex::Regex;
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    io::{self, Write},
+    path::Path,
+};
 
 pub(crate) fn get_runs() -> std::vec::Vec<Box<dyn PuzzleRun>> {
     vec![Box::new(Part2)]
@@ -24,20 +39,416 @@ impl<'a> State<'a> {
         }
     }
 }
+#[derive(Debug, Clone, Copy)]
 enum Dir {
     Left,
     Right,
 }
 
-impl From<u8> for Dir {
-    fn from(s: u8) -> Self {
+impl TryFrom<u8> for Dir {
+    type Error = anyhow::Error;
+
+    fn try_from(s: u8) -> Result<Self, Self::Error> {
         match s {
-            b'L' => Dir::Left,
-            b'R' => Dir::Right,
-            _ => panic!("bad instruction"),
+            b'L' => Ok(Dir::Left),
+            b'R' => Ok(Dir::Right),
+            _ => anyhow::bail!("bad instruction: {}", s as char),
+        }
+    }
+}
+
+impl Display for Dir {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Dir::Left => write!(f, "L"),
+            Dir::Right => write!(f, "R"),
+        }
+    }
+}
+
+lazy_static! {
+    static ref NODE_RE: Regex = Regex::new(r"(\w+) = \((\w+), (\w+)\)").unwrap();
+}
+
+/**
+ * The instructions and the nodes, sorted by label, with each node's left and
+ * right resolved to indexes
+ */
+struct Network<'a> {
+    instructions: std::vec::Vec<Dir>,
+    states: Vec<'a, State<'a>>,
+}
+
+impl<'a> Network<'a> {
+    /**
+     * Fails on a line that isn't a node, a label used twice, or a node that
+     * goes to a label that isn't defined
+     */
+    fn parse(input: &'a str, bump: &'a Bump) -> anyhow::Result<Self> {
+        let mut lines = input.lines();
+        let instructions: std::vec::Vec<Dir> = lines
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .bytes()
+            .map(Dir::try_from)
+            .collect::<anyhow::Result<_>>()?;
+        if instructions.is_empty() {
+            anyhow::bail!("no instructions");
+        }
+        lines.next();
+        let mut states = Vec::new_in(bump);
+
+        let mut tmp_map: HashMap<&str, (&str, &str)> = Default::default();
+
+        for line in lines.map(|s| s.trim()) {
+            match NODE_RE.captures(line).map(|c| c.extract()) {
+                Some((_, [label, left, right])) => {
+                    states.push(State::new(label));
+                    if tmp_map.insert(label, (left, right)).is_some() {
+                        anyhow::bail!("node {} is defined twice", label);
+                    }
+                }
+                None => anyhow::bail!("not a node: {}", line),
+            }
         }
+        states.sort_by_cached_key(|k| k.label);
+
+        for (label, (left, right)) in tmp_map.into_iter() {
+            let find = |target: &str| {
+                states
+                    .binary_search_by_key(&target, |k| k.label)
+                    .map_err(|_| anyhow::anyhow!("node {} goes to unknown node {}", label, target))
+            };
+            let (left, right) = (find(left)?, find(right)?);
+            let state_idx = states.binary_search_by_key(&label, |k| k.label).unwrap();
+            states[state_idx].left = Some(left);
+            states[state_idx].right = Some(right);
+        }
+        Ok(Self {
+            instructions,
+            states,
+        })
+    }
+
+    fn find(&self, label: &str) -> Option<usize> {
+        self.states.binary_search_by_key(&label, |k| k.label).ok()
+    }
+
+    fn next(&self, node: usize, dir: Dir) -> usize {
+        match dir {
+            Dir::Left => self.states[node].left.unwrap(),
+            Dir::Right => self.states[node].right.unwrap(),
+        }
+    }
+
+    /**
+     * One move from `node` when about to follow instruction `i`
+     */
+    fn step(&self, (node, i): (usize, usize)) -> (usize, usize) {
+        (
+            self.next(node, self.instructions[i]),
+            (i + 1) % self.instructions.len(),
+        )
+    }
+
+    /**
+     * The left and right moves as a graph, with each node at the same index
+     * as in `states`. A node whose left and right are the same gets one edge.
+     */
+    fn graph(&self) -> DiGraph<&'a str, String> {
+        let mut graph = DiGraph::with_capacity(self.states.len(), 2 * self.states.len());
+        for state in self.states.iter() {
+            graph.add_node(state.label);
+        }
+        for (i, state) in self.states.iter().enumerate() {
+            let (left, right) = (state.left.unwrap(), state.right.unwrap());
+            if left == right {
+                graph.add_edge(NodeIndex::new(i), NodeIndex::new(left), "LR".to_string());
+            } else {
+                graph.add_edge(
+                    NodeIndex::new(i),
+                    NodeIndex::new(left),
+                    Dir::Left.to_string(),
+                );
+                graph.add_edge(
+                    NodeIndex::new(i),
+                    NodeIndex::new(right),
+                    Dir::Right.to_string(),
+                );
+            }
+        }
+        graph
+    }
+
+    fn write_dot<W: Write>(&self, mut out: W) -> io::Result<()> {
+        let graph = self.graph();
+        write!(out, "{}", Dot::new(&graph))
+    }
+
+    /**
+     * Nodes ending in `A`, where walks start
+     */
+    fn starts(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.states.len()).filter(|&i| self.states[i].label.ends_with('A'))
+    }
+
+    fn reachable_from(
+        &self,
+        graph: &DiGraph<&'a str, String>,
+        start: usize,
+    ) -> std::vec::Vec<bool> {
+        let mut seen = vec![false; self.states.len()];
+        let mut dfs = Dfs::new(graph, NodeIndex::new(start));
+        while let Some(n) = dfs.next(graph) {
+            seen[n.index()] = true;
+        }
+        seen
+    }
+
+    /**
+     * Nodes that no walk from a start can get to
+     */
+    fn unreachable(&self) -> std::vec::Vec<&'a str> {
+        let graph = self.graph();
+        let mut seen = vec![false; self.states.len()];
+        for start in self.starts() {
+            for (i, r) in self.reachable_from(&graph, start).into_iter().enumerate() {
+                seen[i] |= r;
+            }
+        }
+        self.labels_where(|i| !seen[i])
+    }
+
+    /**
+     * Nodes that go back to themselves whichever way they are left
+     */
+    fn sinks(&self) -> std::vec::Vec<&'a str> {
+        self.labels_where(|i| self.states[i].left == Some(i) && self.states[i].right == Some(i))
+    }
+
+    /**
+     * The strongly connected components a walk from `start` can reach, each
+     * sorted by label, in the order the walk could reach them
+     */
+    fn components_from(&self, start: usize) -> std::vec::Vec<std::vec::Vec<&'a str>> {
+        let graph = self.graph();
+        let seen = self.reachable_from(&graph, start);
+        let reachable = NodeFiltered::from_fn(&graph, |n| seen[n.index()]);
+        let mut components: std::vec::Vec<std::vec::Vec<&str>> = tarjan_scc(&reachable)
+            .into_iter()
+            .map(|c| {
+                let mut labels: std::vec::Vec<&str> = c.iter().map(|&n| graph[n]).collect();
+                labels.sort_unstable();
+                labels
+            })
+            .collect();
+        // tarjan gives them sinks first
+        components.reverse();
+        components
+    }
+
+    fn labels_where<F: Fn(usize) -> bool>(&self, keep: F) -> std::vec::Vec<&'a str> {
+        (0..self.states.len())
+            .filter(|&i| keep(i))
+            .map(|i| self.states[i].label)
+            .collect()
+    }
+
+    fn write_analysis<W: Write>(&self, mut out: W) -> io::Result<()> {
+        writeln!(
+            out,
+            "{} nodes, {} instructions",
+            self.states.len(),
+            self.instructions.len()
+        )?;
+        writeln!(out, "unreachable: {}", self.unreachable().join(" "))?;
+        writeln!(out, "sinks: {}", self.sinks().join(" "))?;
+        for start in self.starts() {
+            let components = self.components_from(start);
+            writeln!(
+                out,
+                "from {}: {} components: {}",
+                self.states[start].label,
+                components.len(),
+                components
+                    .iter()
+                    .map(|c| format!("[{}]", c.join(" ")))
+                    .collect::<std::vec::Vec<_>>()
+                    .join(" ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/**
+ * Check the network, then write it as Graphviz DOT to `dot` and/or print
+ * what can reach what
+ */
+pub(crate) fn analyse(dot: Option<&Path>, report: bool) -> anyhow::Result<()> {
+    let bump = Bump::new();
+    let network = Network::parse(read_file("input/day8.txt")?, &bump)?;
+    if let Some(path) = dot {
+        let mut out = io::BufWriter::new(std::fs::File::create(path)?);
+        network.write_dot(&mut out)?;
+        out.flush()?;
+    }
+    if report {
+        network.write_analysis(io::stdout().lock())?;
+    }
+    Ok(())
+}
+
+/**
+ * Where one ghost's walk goes. Its (node, instruction) state repeats, so
+ * after `cycle.prefix` steps it goes round every `cycle.period` steps.
+ * `hits` are the steps before it first comes back round at which it stands
+ * on an end node.
+ */
+#[derive(Debug)]
+struct Ghost {
+    cycle: Cycle,
+    hits: std::vec::Vec<u64>,
+}
+
+impl Ghost {
+    fn walk<F: Fn(&str) -> bool>(network: &Network, start: usize, is_end: F) -> Self {
+        let history = History::find((start, 0), |&s| network.step(s));
+        let hits = history
+            .states
+            .iter()
+            .enumerate()
+            .filter(|(_, (node, _))| is_end(network.states[*node].label))
+            .map(|(t, _)| t as u64)
+            .collect();
+        Self {
+            cycle: history.cycle,
+            hits,
+        }
+    }
+
+    fn prefix(&self) -> u64 {
+        self.cycle.prefix as u64
+    }
+
+    fn period(&self) -> u64 {
+        self.cycle.period as u64
+    }
+
+    fn prefix_hits(&self) -> impl Iterator<Item = u64> + '_ {
+        self.hits.iter().copied().filter(|&t| t < self.prefix())
+    }
+
+    /**
+     * Steps within the first time round the cycle that land on an end node.
+     * Every later hit is one of these plus some number of periods.
+     */
+    fn cycle_hits(&self) -> impl Iterator<Item = u64> + '_ {
+        self.hits.iter().copied().filter(|&t| t >= self.prefix())
+    }
+
+    fn is_hit(&self, t: u64) -> bool {
+        let t = self.cycle.reduce(t as usize) as u64;
+        self.hits.binary_search(&t).is_ok()
+    }
+}
+
+/**
+ * The shortcut of taking the LCM of every ghost's first hit only works when
+ * each ghost lands on an end node at exactly the multiples of its period
+ */
+#[derive(Debug, PartialEq, Eq)]
+struct Assumptions {
+    no_prefix_hits: bool,
+    one_hit_per_cycle: bool,
+    hit_at_period: bool,
+}
+
+impl Assumptions {
+    fn check(ghosts: &[Ghost]) -> Self {
+        Self {
+            no_prefix_hits: ghosts.iter().all(|g| g.prefix_hits().next().is_none()),
+            one_hit_per_cycle: ghosts.iter().all(|g| g.cycle_hits().count() == 1),
+            hit_at_period: ghosts
+                .iter()
+                .all(|g| g.cycle_hits().all(|t| t == g.period())),
+        }
+    }
+
+    fn lcm_holds(&self) -> bool {
+        self.no_prefix_hits && self.one_hit_per_cycle && self.hit_at_period
+    }
+}
+
+impl Display for Assumptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let yes_no = |b: bool| if b { "yes" } else { "no" };
+        writeln!(
+            f,
+            "no end nodes before cycling: {}",
+            yes_no(self.no_prefix_hits)
+        )?;
+        writeln!(
+            f,
+            "one end node per cycle: {}",
+            yes_no(self.one_hit_per_cycle)
+        )?;
+        write!(f, "end node at each period: {}", yes_no(self.hit_at_period))
     }
 }
+
+/**
+ * The LCM of every ghost's period, which is when they all hit at once if the
+ * `Assumptions` hold
+ */
+fn period_lcm(ghosts: &[Ghost]) -> anyhow::Result<u64> {
+    ghosts.iter().try_fold(1, |acc: u64, g| {
+        (acc / gcd(acc, g.period()))
+            .checked_mul(g.period())
+            .ok_or_else(|| anyhow::anyhow!("LCM of the ghosts' periods overflows a u64"))
+    })
+}
+
+/**
+ * The first step at which every ghost is on an end node at once, if there is
+ * one. Fails if the steps get too big for a `u64` before finding out.
+ */
+fn all_hit(ghosts: &[Ghost]) -> anyhow::Result<Option<u64>> {
+    // until every ghost is cycling, check each step on its own
+    let cycling = ghosts.iter().map(|g| g.prefix()).max().unwrap_or(0);
+    if let Some(t) = (0..cycling).find(|&t| ghosts.iter().all(|g| g.is_hit(t))) {
+        return Ok(Some(t));
+    }
+
+    // after that, a step is a hit for a ghost when it is one of the cycle
+    // hits modulo the period, so line up every choice of hit for each ghost
+    let mut aligned = vec![(0, 1)];
+    for g in ghosts {
+        aligned = aligned
+            .iter()
+            .flat_map(|&a| g.cycle_hits().map(move |h| crt(a, (h, g.period()))))
+            .filter_map(Result::transpose)
+            .collect::<anyhow::Result<_>>()?;
+        aligned.sort_unstable();
+        aligned.dedup();
+    }
+    let firsts = aligned
+        .into_iter()
+        .map(|(r, m)| {
+            if r >= cycling {
+                return Ok(r);
+            }
+            (cycling - r)
+                .div_ceil(m)
+                .checked_mul(m)
+                .and_then(|n| n.checked_add(r))
+                .ok_or_else(|| anyhow::anyhow!("first hit after step {} overflows a u64", cycling))
+        })
+        .collect::<anyhow::Result<std::vec::Vec<u64>>>()?;
+    Ok(firsts.into_iter().min())
+}
+
 struct Part1;
 
 impl PuzzleRun for Part1 {
@@ -64,52 +475,17 @@ impl PuzzleRun for Part1 {
     }
 
     fn run(&self, input: &str) -> String {
-        let mut lines = input.lines();
-        let instructions: std::vec::Vec<Dir> =
-            lines.next().unwrap().bytes().map(|i| i.into()).collect();
-        lines.next();
         let bump = Bump::new();
-        let mut states = Vec::new_in(&bump);
-
-        let mut tmp_map: HashMap<&str, (&str, &str)> = Default::default();
-        let regex = Regex::new(r"(\w+) = \((\w+), (\w+)\)").unwrap();
-
-        for line in lines.map(|s| s.trim()) {
-            match regex.captures(line).map(|c| c.extract()) {
-                Some((_, [label, left, right])) => {
-                    states.push(State::new(label));
-                    tmp_map.insert(label, (left, right));
-                }
-                None => panic!("{}", line),
-            }
-        }
-        states.sort_by_cached_key(|k| k.label);
-
-        for (label, (left, right)) in tmp_map.into_iter() {
-            let state_idx = states.binary_search_by_key(&label, |k| k.label).unwrap();
-            states[state_idx].left = Some(states.binary_search_by_key(&left, |k| k.label).unwrap());
-            states[state_idx].right =
-                Some(states.binary_search_by_key(&right, |k| k.label).unwrap());
-        }
-
-        let mut current_state = states.binary_search_by_key(&"AAA", |k| k.label).unwrap();
+        let network = Network::parse(input, &bump).unwrap();
+        let mut current_state = network.find("AAA").unwrap();
         let mut moves: u32 = 0;
 
-        while states[current_state].label != "ZZZ" {
-            for i in instructions.iter() {
-                moves += 1;
-                match i {
-                    Dir::Left => {
-                        current_state = states[current_state].left.unwrap();
-                    }
-                    Dir::Right => {
-                        current_state = states[current_state].right.unwrap();
-                    }
-                }
-                if states[current_state].label == "ZZZ" {
-                    break;
-                }
+        for &i in network.instructions.iter().cycle() {
+            if network.states[current_state].label == "ZZZ" {
+                break;
             }
+            moves += 1;
+            current_state = network.next(current_state, i);
         }
         format!("{}", moves)
     }
@@ -135,76 +511,27 @@ impl PuzzleRun for Part2 {
     }
 
     fn run(&self, input: &str) -> String {
-        let mut lines = input.lines();
-        let instructions: std::vec::Vec<Dir> =
-            lines.next().unwrap().bytes().map(|i| i.into()).collect();
-        lines.next();
         let bump = Bump::new();
-        let mut states = Vec::new_in(&bump);
-
-        let mut tmp_map: HashMap<&str, (&str, &str)> = Default::default();
-        let regex = Regex::new(r"(\w+) = \((\w+), (\w+)\)").unwrap();
-
-        for line in lines.map(|s| s.trim()) {
-            match regex.captures(line).map(|c| c.extract()) {
-                Some((_, [label, left, right])) => {
-                    states.push(State::new(label));
-                    tmp_map.insert(label, (left, right));
-                }
-                None => panic!("{}", line),
-            }
-        }
-        states.sort_by_cached_key(|k| k.label);
-
-        for (label, (left, right)) in tmp_map.into_iter() {
-            let state_idx = states.binary_search_by_key(&label, |k| k.label).unwrap();
-            states[state_idx].left = Some(states.binary_search_by_key(&left, |k| k.label).unwrap());
-            states[state_idx].right =
-                Some(states.binary_search_by_key(&right, |k| k.label).unwrap());
-        }
-
-        let mut currents: std::vec::Vec<usize> = states
+        let network = Network::parse(input, &bump).unwrap();
+        let ghosts: std::vec::Vec<Ghost> = network
+            .states
             .iter()
             .enumerate()
-            .filter(|(idx, state)| state.label.ends_with('A'))
-            .map(|(idx, state)| idx)
+            .filter(|(_, state)| state.label.ends_with('A'))
+            .map(|(idx, _)| Ghost::walk(&network, idx, |l| l.ends_with('Z')))
             .collect();
 
-        println!("starts: {:?}", currents);
-
-        let mut cycles: std::vec::Vec<u32> = std::vec::Vec::with_capacity(currents.len());
-        let starts = currents.clone();
-
-        for i in 0..currents.len() {
-            let mut moves: u32 = 0;
-            for instruction in instructions.iter().cycle() {
-                moves += 1;
-                match instruction {
-                    Dir::Left => {
-                        currents[i] = states[currents[i]].left.unwrap();
-                    }
-                    Dir::Right => {
-                        currents[i] = states[currents[i]].right.unwrap();
-                    }
-                }
-                if states[currents[i]].label.ends_with('Z') {
-                    println!(
-                        "seq starting with {} ended at {} after {} steps",
-                        states[starts[i]].label, states[currents[i]].label, moves,
-                    );
-                    cycles.push(moves);
-                    println!("{} steps", moves);
-                    break;
-                }
-            }
-        }
-        println!("{:?}", cycles);
-        let mut ans: u64 = 1;
-        while cycles.len() > 0 {
-            ans = lcm(ans, cycles.pop().unwrap() as u64);
-            println!("lcm: {}", ans);
+        let assumptions = Assumptions::check(&ghosts);
+        eprintln!("{}", assumptions);
+        let steps = if assumptions.lcm_holds() {
+            period_lcm(&ghosts).map(Some)
+        } else {
+            all_hit(&ghosts)
+        };
+        match steps.expect("lining up the ghosts") {
+            Some(steps) => format!("{}", steps),
+            None => "never".to_string(),
         }
-        format!("{}", ans)
     }
 }
 
@@ -222,4 +549,202 @@ mod test {
     fn test_part2() {
         println!("{}", Part2.run(Part2.input_data().unwrap()));
     }
+
+    const GHOSTS: &str = "LR
+
+        11A = (11B, XXX)
+        11B = (XXX, 11Z)
+        11Z = (11B, XXX)
+        22A = (22B, XXX)
+        22B = (22C, 22C)
+        22C = (22Z, 22Z)
+        22Z = (22B, 22B)
+        XXX = (XXX, XXX)";
+
+    /**
+     * Move every ghost one step at a time until they are all on an end node
+     */
+    fn brute_force(network: &Network, limit: u64) -> Option<u64> {
+        let mut ghosts: std::vec::Vec<usize> = (0..network.states.len())
+            .filter(|&i| network.states[i].label.ends_with('A'))
+            .collect();
+        for t in 0..limit {
+            if ghosts
+                .iter()
+                .all(|&g| network.states[g].label.ends_with('Z'))
+            {
+                return Some(t);
+            }
+            let dir = network.instructions[t as usize % network.instructions.len()];
+            for g in ghosts.iter_mut() {
+                *g = network.next(*g, dir);
+            }
+        }
+        None
+    }
+
+    fn ghosts(network: &Network) -> std::vec::Vec<Ghost> {
+        (0..network.states.len())
+            .filter(|&i| network.states[i].label.ends_with('A'))
+            .map(|i| Ghost::walk(network, i, |l| l.ends_with('Z')))
+            .collect()
+    }
+
+    #[test]
+    fn test_example() {
+        let bump = Bump::new();
+        let network = Network::parse(GHOSTS, &bump).unwrap();
+        let ghosts = ghosts(&network);
+        assert_eq!(
+            ghosts[0].cycle,
+            Cycle {
+                prefix: 1,
+                period: 2
+            }
+        );
+        assert_eq!(ghosts[0].hits, vec![2]);
+        assert_eq!(ghosts[1].hits, vec![3, 6]);
+        let assumptions = Assumptions::check(&ghosts);
+        assert_eq!(
+            assumptions,
+            Assumptions {
+                no_prefix_hits: true,
+                one_hit_per_cycle: false,
+                hit_at_period: false
+            }
+        );
+        assert_eq!(all_hit(&ghosts).unwrap(), Some(6));
+        assert_eq!(Part2.run(GHOSTS), "6");
+    }
+
+    #[test]
+    fn test_lcm_fails() {
+        // hits at 2, 5, 8, ... and at 1, 5, 9, ..., so the LCM of the
+        // periods (12) isn't a hit for either
+        let input = "L
+
+        1A = (1B, 1B)
+        1B = (1Z, 1Z)
+        1Z = (1C, 1C)
+        1C = (1B, 1B)
+        2A = (2Z, 2Z)
+        2Z = (2B, 2B)
+        2B = (2C, 2C)
+        2C = (2D, 2D)
+        2D = (2Z, 2Z)";
+        let bump = Bump::new();
+        let network = Network::parse(input, &bump).unwrap();
+        let ghosts = ghosts(&network);
+        assert!(!Assumptions::check(&ghosts).lcm_holds());
+        assert_eq!(all_hit(&ghosts).unwrap(), Some(5));
+        assert_eq!(brute_force(&network, 100), Some(5));
+        assert_eq!(Part2.run(input), "5");
+    }
+
+    #[test]
+    fn test_against_brute_force() {
+        let mut state: u64 = 3;
+        let mut next = |n: u64| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 33) % n
+        };
+        for _ in 0..200 {
+            let labels: std::vec::Vec<String> = (0..6)
+                .map(|i| format!("N{}{}", i, ["A", "Z", "B", "B"][next(4) as usize]))
+                .collect();
+            let instructions: String = (0..1 + next(4))
+                .map(|_| ["L", "R"][next(2) as usize])
+                .collect();
+            let mut input = format!("{}\n\n", instructions);
+            for label in &labels {
+                let (l, r) = (next(6) as usize, next(6) as usize);
+                input += &format!("{} = ({}, {})\n", label, labels[l], labels[r]);
+            }
+
+            let bump = Bump::new();
+            let network = Network::parse(input.trim(), &bump).unwrap();
+            let expected = brute_force(&network, 5_000);
+            let found = all_hit(&ghosts(&network)).unwrap();
+            match expected {
+                Some(_) => assert_eq!(found, expected, "{}", input),
+                None => assert!(found.is_none_or(|t| t >= 5_000), "{}", input),
+            }
+        }
+    }
+
+    #[test]
+    fn test_overflow() {
+        let ghost = |period: u64, hit: u64| Ghost {
+            cycle: Cycle {
+                prefix: 0,
+                period: period as usize,
+            },
+            hits: vec![hit],
+        };
+        let ghosts = [ghost(u64::MAX, 1), ghost(u64::MAX - 1, 2)];
+        assert!(all_hit(&ghosts).is_err());
+        assert!(period_lcm(&ghosts).is_err());
+
+        // no common step at all is still an answer
+        let ghosts = [ghost(4, 1), ghost(6, 2)];
+        assert_eq!(all_hit(&ghosts).unwrap(), None);
+        assert_eq!(period_lcm(&ghosts).unwrap(), 12);
+    }
+
+    #[test]
+    fn test_validate() {
+        let bump = Bump::new();
+        let parse = |input| {
+            Network::parse(input, &bump)
+                .map(|_| ())
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(
+            parse("LR\n\nAAA = (BBB, ZZZ)\nBBB = (AAA, AAA)"),
+            "node AAA goes to unknown node ZZZ"
+        );
+        assert_eq!(
+            parse("LR\n\nAAA = (AAA, AAA)\nAAA = (AAA, AAA)"),
+            "node AAA is defined twice"
+        );
+        assert_eq!(parse("LXR\n\nAAA = (AAA, AAA)"), "bad instruction: X");
+        assert_eq!(parse("L\n\nAAA = AAA"), "not a node: AAA = AAA");
+    }
+
+    #[test]
+    fn test_analysis() {
+        let input = format!("{}\n        YYY = (XXX, 22C)", GHOSTS);
+        let bump = Bump::new();
+        let network = Network::parse(&input, &bump).unwrap();
+        assert_eq!(network.unreachable(), vec!["YYY"]);
+        assert_eq!(network.sinks(), vec!["XXX"]);
+
+        let start = network.find("11A").unwrap();
+        assert_eq!(
+            network.components_from(start),
+            vec![vec!["11A"], vec!["11B", "11Z"], vec!["XXX"]]
+        );
+        let start = network.find("22A").unwrap();
+        assert_eq!(
+            network.components_from(start),
+            vec![vec!["22A"], vec!["22B", "22C", "22Z"], vec!["XXX"]]
+        );
+
+        let mut out = vec![];
+        network.write_analysis(&mut out).unwrap();
+        let report = String::from_utf8(out).unwrap();
+        assert!(report.contains("unreachable: YYY\n"));
+        assert!(report.contains("from 22A: 3 components: [22A] [22B 22C 22Z] [XXX]\n"));
+
+        let mut out = vec![];
+        network.write_dot(&mut out).unwrap();
+        let dot = String::from_utf8(out).unwrap();
+        assert!(dot.starts_with("digraph {"));
+        assert!(dot.contains(r#"label = "11Z""#));
+        assert_eq!(dot.matches(r#"label = "LR""#).count(), 4);
+        assert_eq!(dot.matches(" -> ").count(), 14);
+    }
 }
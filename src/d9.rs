@@ -1,32 +1,137 @@
 use crate::{read_file, PuzzleRun};
+use num::{
+    traits::{CheckedAdd, CheckedDiv, CheckedMul, CheckedSub},
+    One, Zero,
+};
+use std::fmt::Display;
 
 pub(crate) fn get_runs() -> std::vec::Vec<Box<dyn PuzzleRun>> {
     vec![Box::new(Part1)]
 }
 
-fn parse(line: &str) -> Vec<i32> {
-    line.split_ascii_whitespace()
-        .map(|s| s.parse().unwrap())
-        .collect()
+fn parse<N: std::str::FromStr>(line: &str) -> anyhow::Result<Vec<N>>
+where
+    N::Err: std::error::Error + Send + Sync + 'static,
+{
+    Ok(line
+        .split_ascii_whitespace()
+        .map(str::parse)
+        .collect::<Result<_, _>>()?)
 }
 
-fn solve_part1(input: Vec<i32>) -> i32 {
-    let mut diffs = vec![input];
-    while !diffs[diffs.len() - 1].iter().all(|&i| i == 0) {
-        let mut diff = Vec::with_capacity(diffs[0].len());
-        for w in diffs.last().unwrap().windows(2) {
-            diff.push(w[1] - w[0]);
+/**
+ * The polynomial through a sequence, found from its difference table. Only
+ * the first entry of each row is kept: those are the coefficients of the
+ * Newton forward-difference form
+ *
+ *   f(x) = c0 + c1 C(x, 1) + c2 C(x, 2) + ...
+ *
+ * where x is the position in the sequence, counting from 0.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Extrapolator<N> {
+    coefficients: Vec<N>,
+    len: usize,
+}
+
+impl<N> Extrapolator<N>
+where
+    N: Clone + Zero + One + CheckedAdd + CheckedSub + CheckedMul + CheckedDiv + From<i64>,
+{
+    /**
+     * Fails if the differences run out before reaching a row of zeros, since
+     * then the sequence doesn't pin down a polynomial
+     */
+    fn fit(values: &[N]) -> anyhow::Result<Self> {
+        let mut coefficients = vec![];
+        let mut row = values.to_vec();
+        while !row.iter().all(Zero::is_zero) || row.is_empty() {
+            if row.len() < 2 {
+                anyhow::bail!(
+                    "differences of {} values never reach all zeros",
+                    values.len()
+                );
+            }
+            coefficients.push(row[0].clone());
+            row = row
+                .windows(2)
+                .map(|w| w[1].checked_sub(&w[0]).ok_or_else(overflow))
+                .collect::<anyhow::Result<_>>()?;
         }
-        diffs.push(diff);
+        Ok(Self {
+            coefficients,
+            len: values.len(),
+        })
+    }
+
+    /**
+     * The zero polynomial has no coefficients and degree 0
+     */
+    fn degree(&self) -> usize {
+        self.coefficients.len().saturating_sub(1)
+    }
+
+    fn coefficients(&self) -> &[N] {
+        &self.coefficients
+    }
+
+    /**
+     * The value at position `x`, which can be past either end
+     */
+    fn at(&self, x: i64) -> anyhow::Result<N> {
+        let mut value = N::zero();
+        // C(x, k), which is an integer for any integer x, so each division
+        // is exact
+        let mut binomial = N::one();
+        for (k, c) in self.coefficients.iter().enumerate() {
+            if k > 0 {
+                let k = k as i64;
+                binomial = binomial
+                    .checked_mul(&N::from(x - k + 1))
+                    .and_then(|b| b.checked_div(&N::from(k)))
+                    .ok_or_else(overflow)?;
+            }
+            let term = c.checked_mul(&binomial).ok_or_else(overflow)?;
+            value = value.checked_add(&term).ok_or_else(overflow)?;
+        }
+        Ok(value)
+    }
+
+    /**
+     * The value `steps` after the last one
+     */
+    fn ahead(&self, steps: u32) -> anyhow::Result<N> {
+        self.at(self.len as i64 - 1 + steps as i64)
     }
-    while diffs.len() > 1 {
-        let diff = diffs.pop().unwrap();
-        let l = diffs.len();
-        let dr: &mut Vec<i32> = &mut diffs[l - 1];
-        dr.push(dr.last().unwrap() + diff.last().unwrap());
+
+    /**
+     * The value `steps` before the first one
+     */
+    fn behind(&self, steps: u32) -> anyhow::Result<N> {
+        self.at(-(steps as i64))
     }
-    *diffs[0].last().unwrap()
 }
+
+fn overflow() -> anyhow::Error {
+    anyhow::anyhow!("extrapolation overflows")
+}
+
+impl<N: Display + Zero> Display for Extrapolator<N> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.coefficients.is_empty() {
+            return write!(f, "0");
+        }
+        for (k, c) in self.coefficients.iter().enumerate() {
+            match k {
+                0 => write!(f, "{}", c)?,
+                _ if c.is_zero() => {}
+                _ => write!(f, " + {} C(x, {})", c, k)?,
+            }
+        }
+        Ok(())
+    }
+}
+
 struct Part1;
 
 impl PuzzleRun for Part1 {
@@ -39,30 +144,16 @@ impl PuzzleRun for Part1 {
             "{}",
             input
                 .lines()
-                .map(|line| solve_part1(parse(line.trim())))
-                .sum::<i32>()
+                .map(
+                    |line| Extrapolator::<i64>::fit(&parse(line.trim()).unwrap())
+                        .and_then(|e| e.ahead(1))
+                        .unwrap()
+                )
+                .sum::<i64>()
         )
     }
 }
 
-fn solve_part2(input: Vec<i32>) -> i32 {
-    let mut diffs = vec![input];
-    while !diffs[diffs.len() - 1].iter().all(|&i| i == 0) {
-        let mut diff = Vec::with_capacity(diffs[0].len());
-        for w in diffs.last().unwrap().windows(2) {
-            diff.push(w[1] - w[0]);
-        }
-        diffs.push(diff);
-    }
-    while diffs.len() > 1 {
-        let diff = diffs.pop().unwrap();
-        let l = diffs.len();
-        let dr: &mut Vec<i32> = &mut diffs[l - 1];
-        dr.insert(0, dr.first().unwrap() - diff.first().unwrap());
-    }
-    *diffs[0].first().unwrap()
-}
-
 struct Part2;
 
 impl PuzzleRun for Part2 {
@@ -79,8 +170,12 @@ impl PuzzleRun for Part2 {
             "{}",
             input
                 .lines()
-                .map(|line| solve_part2(parse(line.trim())))
-                .sum::<i32>()
+                .map(
+                    |line| Extrapolator::<i64>::fit(&parse(line.trim()).unwrap())
+                        .and_then(|e| e.behind(1))
+                        .unwrap()
+                )
+                .sum::<i64>()
         )
     }
 }
@@ -88,6 +183,11 @@ impl PuzzleRun for Part2 {
 mod test {
 
     use super::*;
+    use num::BigInt;
+
+    const EXAMPLE: &str = "0 3 6 9 12 15
+        1 3 6 10 15 21
+        10 13 16 21 30 45";
 
     #[test]
     fn test_part1() {
@@ -98,4 +198,68 @@ mod test {
     fn test_part2() {
         println!("{}", Part2.run(Part2.input_data().unwrap()));
     }
+
+    #[test]
+    fn test_example() {
+        assert_eq!(Part1.run(EXAMPLE), "114");
+        assert_eq!(Part2.run(EXAMPLE), "2");
+    }
+
+    #[test]
+    fn test_newton_form() {
+        let e = Extrapolator::<i64>::fit(&[10, 13, 16, 21, 30, 45]).unwrap();
+        assert_eq!(e.degree(), 3);
+        assert_eq!(e.coefficients(), &[10, 3, 0, 2]);
+        assert_eq!(e.to_string(), "10 + 3 C(x, 1) + 2 C(x, 3)");
+        assert_eq!(e.ahead(1).unwrap(), 68);
+        assert_eq!(e.behind(1).unwrap(), 5);
+        for (x, &v) in [10, 13, 16, 21, 30, 45].iter().enumerate() {
+            assert_eq!(e.at(x as i64).unwrap(), v);
+        }
+
+        let zero = Extrapolator::<i64>::fit(&[0, 0, 0]).unwrap();
+        assert_eq!((zero.degree(), zero.to_string()), (0, "0".to_string()));
+        assert_eq!(zero.behind(5).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_many_steps() {
+        // x^3 - 2x at x = 0..6
+        let cube = |x: i64| x * x * x - 2 * x;
+        let values: Vec<i64> = (0..6).map(cube).collect();
+        let e = Extrapolator::fit(&values).unwrap();
+        assert_eq!(e.degree(), 3);
+        for k in 1..50 {
+            assert_eq!(e.ahead(k).unwrap(), cube(5 + k as i64));
+            assert_eq!(e.behind(k).unwrap(), cube(-(k as i64)));
+        }
+
+        let wide =
+            Extrapolator::fit(&values.iter().map(|&v| v as i128).collect::<Vec<_>>()).unwrap();
+        assert_eq!(wide.ahead(1_000_000).unwrap(), {
+            let x = 1_000_005i128;
+            x * x * x - 2 * x
+        });
+        assert!(e.ahead(10_000_000).is_err());
+
+        let big = Extrapolator::fit(&values.iter().map(|&v| BigInt::from(v)).collect::<Vec<_>>())
+            .unwrap();
+        let x = BigInt::from(5i64 + u32::MAX as i64);
+        assert_eq!(
+            big.ahead(u32::MAX).unwrap(),
+            &x * &x * &x - BigInt::from(2) * &x
+        );
+    }
+
+    #[test]
+    fn test_no_zero_row() {
+        assert!(Extrapolator::<i64>::fit(&[1, 2, 4, 8]).is_err());
+        assert!(Extrapolator::<i64>::fit(&[]).is_err());
+        assert!(Extrapolator::<i64>::fit(&[7]).is_err());
+        assert_eq!(
+            Extrapolator::<i64>::fit(&[7, 7]).unwrap().ahead(3).unwrap(),
+            7
+        );
+        assert!(parse::<i64>("1 x 3").is_err());
+    }
 }
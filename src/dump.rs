@@ -0,0 +1,100 @@
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+
+/**
+ * How `--dump-parsed` writes a model: readable JSON, or a compact bincode
+ * snapshot that loads back quickly
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DumpFormat {
+    Json,
+    Bincode,
+}
+
+impl DumpFormat {
+    pub(crate) fn for_path(path: &Path) -> anyhow::Result<Self> {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => Ok(Self::Json),
+            Some("bin") => Ok(Self::Bincode),
+            _ => anyhow::bail!("can't tell dump format from {}", path.display()),
+        }
+    }
+}
+
+/**
+ * Where run `i` of a day with `runs` runs dumps its model, so that each file
+ * holds exactly one. A single run uses `path` as given; otherwise the run
+ * number, counting from 1, goes before the extension: `day5-2.json`.
+ */
+pub(crate) fn path_for_run(path: &Path, i: usize, runs: usize) -> PathBuf {
+    if runs <= 1 {
+        return path.to_path_buf();
+    }
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let mut name = format!("{}-{}", stem, i + 1);
+    if let Some(ext) = path.extension() {
+        name = format!("{}.{}", name, ext.to_string_lossy());
+    }
+    path.with_file_name(name)
+}
+
+pub(crate) fn write_model<M: Serialize>(
+    model: &M,
+    out: &mut dyn Write,
+    format: DumpFormat,
+) -> anyhow::Result<()> {
+    match format {
+        DumpFormat::Json => serde_json::to_writer_pretty(out, model)?,
+        DumpFormat::Bincode => bincode::serialize_into(out, model)?,
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::grid::{Dir, Grid};
+
+    #[test]
+    fn test_format_for_path() {
+        assert_eq!(
+            DumpFormat::for_path(Path::new("out/day5.json")).unwrap(),
+            DumpFormat::Json
+        );
+        assert_eq!(
+            DumpFormat::for_path(Path::new("day5.bin")).unwrap(),
+            DumpFormat::Bincode
+        );
+        assert!(DumpFormat::for_path(Path::new("day5")).is_err());
+    }
+
+    #[test]
+    fn test_path_for_run() {
+        let path = Path::new("out/day5.json");
+        assert_eq!(path_for_run(path, 0, 1), path);
+        assert_eq!(path_for_run(path, 0, 2), Path::new("out/day5-1.json"));
+        assert_eq!(path_for_run(path, 1, 2), Path::new("out/day5-2.json"));
+    }
+
+    #[test]
+    fn test_grid_round_trip() {
+        let g: Grid<u16> = Grid::new_from(3, 2, |x, y| (x + 10 * y) as u16);
+
+        let mut json = vec![];
+        write_model(&(&g, Dir::W), &mut json, DumpFormat::Json).unwrap();
+        let (back, dir): (Grid<u16>, Dir) = serde_json::from_slice(&json).unwrap();
+        assert_eq!((back.width, back.height, dir), (3, 2, Dir::W));
+        assert_eq!(*back.get(2, 1), 12);
+
+        let mut bin = vec![];
+        write_model(&g, &mut bin, DumpFormat::Bincode).unwrap();
+        assert!(bin.len() < json.len());
+        let back: Grid<u16> = bincode::deserialize(&bin).unwrap();
+        assert_eq!(*back.get(1, 1), 11);
+    }
+}
@@ -1,7 +1,14 @@
 use num_traits::Zero;
-use std::{fmt::Display, ops::AddAssign, str::FromStr};
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    fmt::Display,
+    ops::{AddAssign, BitAndAssign, BitOrAssign, Not},
+    str::FromStr,
+};
 
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub(crate) struct Grid<T> {
     data: Box<[T]>,
     pub(crate) width: usize,
@@ -60,54 +67,35 @@ impl<T> Grid<T> {
     pub(crate) fn coord(&self, x: usize, y: usize) -> usize {
         y * self.width + x
     }
+}
 
-    pub(crate) fn distance_between(
-        &self,
-        source_x: usize,
-        source_y: usize,
-        target_x: usize,
-        target_y: usize,
-        this_dir: Dir,
-    ) -> usize {
-        match this_dir {
-            Dir::E => {
-                assert!(source_y == target_y && source_x > target_x);
-                source_x - target_x
-            }
-
-            Dir::W => {
-                assert!(source_y == target_y && source_x < target_x);
-                target_x - source_x
-            }
-            Dir::N => {
-                assert!(source_x == target_x && source_y < target_y);
-                target_y - source_y
-            }
-            Dir::S => {
-                assert!(source_x == target_x && source_y > target_y);
-                source_y - target_y
-            }
+fn fmt_cells<'a, T: Display + 'a, F: Fn(usize, usize) -> &'a T>(
+    width: usize,
+    height: usize,
+    get: F,
+    f: &mut std::fmt::Formatter<'_>,
+) -> std::fmt::Result {
+    let mut s = format!("Grid ({} by {}):\n", width, height);
+    for row in 0..height {
+        for col in 0..width {
+            s.push('|');
+            s.push_str(&get(col, row).to_string());
+            s.push('|');
         }
+        s.push('\n');
     }
+    s.push('\n');
+    write!(f, "{}", s)
 }
 
 impl<T: Display> Display for Grid<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut s = format!("Grid ({} by {}):\n", self.width, self.height);
-        for row in 0..self.height {
-            for col in 0..self.width {
-                s.push('|');
-                s.push_str(&self.get(col, row).to_string());
-                s.push('|');
-            }
-            s.push('\n');
-        }
-        s.push('\n');
-        write!(f, "{}", s)
+        fmt_cells(self.width, self.height, |x, y| self.get(x, y), f)
     }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub(crate) enum Dir {
     N,
     S,
@@ -115,6 +103,17 @@ pub(crate) enum Dir {
     W,
 }
 
+impl Dir {
+    pub(crate) fn opposite(self) -> Self {
+        match self {
+            Dir::N => Dir::S,
+            Dir::S => Dir::N,
+            Dir::E => Dir::W,
+            Dir::W => Dir::E,
+        }
+    }
+}
+
 impl<T> Grid<T> {
     pub(crate) fn count_cells<F>(&self, f: F) -> usize
     where
@@ -122,11 +121,26 @@ impl<T> Grid<T> {
     {
         self.data.iter().filter(|&i| f(i)).count()
     }
+}
+
+/**
+ * Cell access and navigation shared by every grid representation, so that
+ * algorithms can be written once for both dense and sparse storage.
+ */
+pub(crate) trait GridLike<T> {
+    fn width(&self) -> usize;
 
-    pub(crate) fn try_next_coord(&self, x: usize, y: usize, dir: Dir) -> Option<(usize, usize)> {
+    fn height(&self) -> usize;
+
+    fn get(&self, x: usize, y: usize) -> &T;
+
+    #[allow(dead_code)]
+    fn get_mut(&mut self, x: usize, y: usize) -> &mut T;
+
+    fn try_next_coord(&self, x: usize, y: usize, dir: Dir) -> Option<(usize, usize)> {
         match dir {
             Dir::E => {
-                if x + 1 < self.width {
+                if x + 1 < self.width() {
                     Some((x + 1, y))
                 } else {
                     None
@@ -147,7 +161,7 @@ impl<T> Grid<T> {
                 }
             }
             Dir::S => {
-                if y + 1 < self.height {
+                if y + 1 < self.height() {
                     Some((x, y + 1))
                 } else {
                     None
@@ -156,57 +170,21 @@ impl<T> Grid<T> {
         }
     }
 
+    #[allow(dead_code)]
     fn try_next(&self, x: usize, y: usize, dir: Dir) -> Option<&T> {
-        self.try_next_coord(x, y, dir)
-            .and_then(|(x, y)| Some(self.get(x, y)))
+        self.try_next_coord(x, y, dir).map(|(x, y)| self.get(x, y))
     }
 
+    #[allow(dead_code)]
     fn try_next_mut(&mut self, x: usize, y: usize, dir: Dir) -> Option<&mut T> {
         self.try_next_coord(x, y, dir)
-            .and_then(|(x, y)| Some(self.get_mut(x, y)))
+            .map(|(x, y)| self.get_mut(x, y))
     }
-}
 
-impl<T> Grid<T>
-where
-    T: AddAssign<T> + Zero + Copy,
-{
-    pub(crate) fn weight_between(&self, x1: usize, y1: usize, x2: usize, y2: usize) -> T {
-        let mut weight = T::zero();
-        if x1 == x2 && y1 == y2 {
-            panic!();
-        }
-        if x1 == x2 {
-            if y2 > y1 {
-                for i in 0..(y2 - y1) {
-                    weight += *self.get(x1, y1 + 1 + i);
-                }
-            } else {
-                for i in 0..(y1 - y2) {
-                    weight += *self.get(x1, y1 - 1 - i);
-                }
-            }
-        } else if y1 == y2 {
-            if x2 > x1 {
-                for i in 0..(x2 - x1) {
-                    weight += *self.get(x1 + 1 + i, y1);
-                }
-            } else {
-                for i in 0..(x1 - x2) {
-                    weight += *self.get(x1 - 1 - i, y1);
-                }
-            }
-        } else {
-            panic!()
-        }
-        weight
-    }
-}
-impl<T> Grid<T> {
     /**
      * Dir in the return tuple is the direction from which we'll move
      */
-    pub(crate) fn cardinal_neighbors(
+    fn cardinal_neighbors(
         &self,
         x: usize,
         y: usize,
@@ -234,7 +212,292 @@ impl<T> Grid<T> {
         }
         v
     }
+}
+
+impl<T> GridLike<T> for Grid<T> {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
 
+    fn get(&self, x: usize, y: usize) -> &T {
+        Grid::get(self, x, y)
+    }
+
+    fn get_mut(&mut self, x: usize, y: usize) -> &mut T {
+        Grid::get_mut(self, x, y)
+    }
+}
+
+/**
+ * A grid that only stores the cells that have been written to; every other
+ * cell reads back as `default`. Coordinates are signed, and the bounds grow
+ * in any direction to cover every cell written so far, never shrinking.
+ *
+ * It isn't a `GridLike` itself, since its `(0, 0)` would move every time a
+ * cell is written above or left of it; take a `view` of a fixed rectangle
+ * instead.
+ */
+#[derive(Clone)]
+pub(crate) struct SparseGrid<T> {
+    cells: HashMap<(i64, i64), T>,
+    default: T,
+    min: (i64, i64),
+    max: (i64, i64),
+}
+
+impl<T> SparseGrid<T> {
+    pub(crate) fn new(default: T) -> Self {
+        Self::with_size(0, 0, default)
+    }
+
+    /**
+     * Starts with bounds covering `(0, 0)` to `(width - 1, height - 1)`
+     */
+    pub(crate) fn with_size(width: usize, height: usize, default: T) -> Self {
+        Self {
+            cells: Default::default(),
+            default,
+            min: (0, 0),
+            max: (width as i64, height as i64),
+        }
+    }
+
+    pub(crate) fn at(&self, x: i64, y: i64) -> &T {
+        self.cells.get(&(x, y)).unwrap_or(&self.default)
+    }
+
+    pub(crate) fn set(&mut self, x: i64, y: i64, val: T) {
+        self.grow_to(x, y);
+        self.cells.insert((x, y), val);
+    }
+
+    #[allow(dead_code)]
+    pub(crate) fn remove(&mut self, x: i64, y: i64) -> Option<T> {
+        self.cells.remove(&(x, y))
+    }
+
+    #[allow(dead_code)]
+    pub(crate) fn contains(&self, x: i64, y: i64) -> bool {
+        self.cells.contains_key(&(x, y))
+    }
+
+    /**
+     * The smallest coordinates inside the bounds
+     */
+    #[allow(dead_code)]
+    pub(crate) fn min(&self) -> (i64, i64) {
+        self.min
+    }
+
+    /**
+     * The largest coordinates inside the bounds. Less than `min` while the
+     * grid is empty.
+     */
+    #[allow(dead_code)]
+    pub(crate) fn max(&self) -> (i64, i64) {
+        (self.max.0 - 1, self.max.1 - 1)
+    }
+
+    /**
+     * Number of cells actually stored, not the area covered by the bounds
+     */
+    #[allow(dead_code)]
+    pub(crate) fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    #[allow(dead_code)]
+    pub(crate) fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = ((i64, i64), &T)> {
+        self.cells.iter().map(|(&c, v)| (c, v))
+    }
+
+    fn grow_to(&mut self, x: i64, y: i64) {
+        if self.min.0 >= self.max.0 || self.min.1 >= self.max.1 {
+            self.min = (x, y);
+            self.max = (x + 1, y + 1);
+            return;
+        }
+        self.min = (i64::min(self.min.0, x), i64::min(self.min.1, y));
+        self.max = (i64::max(self.max.0, x + 1), i64::max(self.max.1, y + 1));
+    }
+
+    /**
+     * The rectangle from `min` to `max` inclusive as a `GridLike`, with
+     * `(0, 0)` at `min`
+     */
+    pub(crate) fn view(&mut self, min: (i64, i64), max: (i64, i64)) -> SparseView<'_, T> {
+        SparseView {
+            grid: self,
+            min,
+            max: (max.0 + 1, max.1 + 1),
+        }
+    }
+}
+
+/**
+ * A fixed rectangle of a `SparseGrid`. The origin stays where it was put
+ * however the grid grows underneath; writes outside the rectangle still
+ * land in the grid, they just aren't part of the view.
+ */
+pub(crate) struct SparseView<'a, T> {
+    grid: &'a mut SparseGrid<T>,
+    min: (i64, i64),
+    max: (i64, i64),
+}
+
+impl<T> SparseView<'_, T> {
+    fn offset(&self, x: usize, y: usize) -> (i64, i64) {
+        (self.min.0 + x as i64, self.min.1 + y as i64)
+    }
+}
+
+impl<T: Clone> GridLike<T> for SparseView<'_, T> {
+    fn width(&self) -> usize {
+        (self.max.0 - self.min.0).max(0) as usize
+    }
+
+    fn height(&self) -> usize {
+        (self.max.1 - self.min.1).max(0) as usize
+    }
+
+    fn get(&self, x: usize, y: usize) -> &T {
+        let (x, y) = self.offset(x, y);
+        self.grid.at(x, y)
+    }
+
+    fn get_mut(&mut self, x: usize, y: usize) -> &mut T {
+        let (x, y) = self.offset(x, y);
+        self.grid.grow_to(x, y);
+        let default = self.grid.default.clone();
+        self.grid.cells.entry((x, y)).or_insert(default)
+    }
+}
+
+impl<T: Display> Display for SparseGrid<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let width = (self.max.0 - self.min.0).max(0) as usize;
+        let height = (self.max.1 - self.min.1).max(0) as usize;
+        fmt_cells(
+            width,
+            height,
+            |x, y| self.at(self.min.0 + x as i64, self.min.1 + y as i64),
+            f,
+        )
+    }
+}
+
+/**
+ * The cells passed walking in a straight line from a starting cell, not
+ * counting the start. Stops at the edge of the grid, or after a fixed number
+ * of cells for a segment.
+ */
+pub(crate) struct Ray<'a, T> {
+    grid: &'a Grid<T>,
+    x: usize,
+    y: usize,
+    dir: Dir,
+    remaining: usize,
+}
+
+impl<'a, T> Iterator for Ray<'a, T> {
+    type Item = (usize, usize, &'a T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let (x, y) = self.grid.try_next_coord(self.x, self.y, self.dir)?;
+        (self.x, self.y) = (x, y);
+        self.remaining -= 1;
+        Some((x, y, self.grid.get(x, y)))
+    }
+}
+
+impl<'a, T> Ray<'a, T> {
+    /**
+     * Stop before the first cell for which `stop` returns true
+     */
+    pub(crate) fn until<F: FnMut(&T) -> bool>(
+        self,
+        mut stop: F,
+    ) -> impl Iterator<Item = (usize, usize, &'a T)> {
+        self.take_while(move |(_, _, v)| !stop(v))
+    }
+
+    pub(crate) fn weight(self) -> T
+    where
+        T: AddAssign<T> + Zero + Copy,
+    {
+        self.fold(T::zero(), |mut acc, (_, _, &v)| {
+            acc += v;
+            acc
+        })
+    }
+}
+
+impl<T> Grid<T> {
+    /**
+     * The cells from (x, y) in `dir` to the edge of the grid, or None if
+     * (x, y) isn't on the grid
+     */
+    pub(crate) fn ray(&self, x: usize, y: usize, dir: Dir) -> Option<Ray<'_, T>> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        Some(Ray {
+            grid: self,
+            x,
+            y,
+            dir,
+            remaining: usize::MAX,
+        })
+    }
+
+    /**
+     * The cells after (x1, y1) up to and including (x2, y2), if the two are
+     * different cells in the same row or column
+     */
+    pub(crate) fn segment(&self, x1: usize, y1: usize, x2: usize, y2: usize) -> Option<Ray<'_, T>> {
+        if x1.max(x2) >= self.width || y1.max(y2) >= self.height {
+            return None;
+        }
+        let (dir, len) = match (x1.cmp(&x2), y1.cmp(&y2)) {
+            (Ordering::Equal, Ordering::Less) => (Dir::S, y2 - y1),
+            (Ordering::Equal, Ordering::Greater) => (Dir::N, y1 - y2),
+            (Ordering::Less, Ordering::Equal) => (Dir::E, x2 - x1),
+            (Ordering::Greater, Ordering::Equal) => (Dir::W, x1 - x2),
+            _ => return None,
+        };
+        Some(Ray {
+            remaining: len,
+            ..self.ray(x1, y1, dir)?
+        })
+    }
+
+    /**
+     * Sum of the cells along `segment`, not counting the first
+     */
+    pub(crate) fn weight_between(&self, x1: usize, y1: usize, x2: usize, y2: usize) -> Option<T>
+    where
+        T: AddAssign<T> + Zero + Copy,
+    {
+        Some(self.segment(x1, y1, x2, y2)?.weight())
+    }
+
+    /**
+     * The cells `min..max` steps away from (x, y) in `dir`, where step 0 is
+     * (x, y) itself, cut short at the edge of the grid. None if that leaves
+     * no cells.
+     */
+    #[allow(dead_code)]
     pub(crate) fn direction_range(
         &self,
         x: usize,
@@ -242,17 +505,440 @@ impl<T> Grid<T> {
         min: usize,
         max: usize,
         dir: Dir,
-    ) -> Vec<(usize, usize)> {
+    ) -> Option<Vec<(usize, usize)>> {
+        let cells: Vec<_> = std::iter::once((x, y))
+            .chain(self.ray(x, y, dir)?.map(|(x, y, _)| (x, y)))
+            .skip(min)
+            .take(max.saturating_sub(min))
+            .collect();
+        if cells.is_empty() {
+            None
+        } else {
+            Some(cells)
+        }
+    }
+}
+
+/**
+ * The result of labelling a grid into connected regions: every cell holds
+ * the index of its region, and `sizes[i]` is the number of cells in region i.
+ */
+pub(crate) struct Regions {
+    pub(crate) labels: Grid<usize>,
+    pub(crate) sizes: Vec<usize>,
+}
+
+impl Regions {
+    pub(crate) fn count(&self) -> usize {
+        self.sizes.len()
+    }
+
+    pub(crate) fn region_of(&self, x: usize, y: usize) -> usize {
+        *self.labels.get(x, y)
+    }
+}
+
+impl<T> Grid<T> {
+    fn fill(
+        &self,
+        mut stack: Vec<(usize, usize)>,
+        seen: &mut Grid<bool>,
+        mut passable: impl FnMut((usize, usize), (usize, usize)) -> bool,
+    ) -> usize {
+        let mut filled = 0;
+        for &(x, y) in stack.iter() {
+            *seen.get_mut(x, y) = true;
+        }
+        while let Some((x, y)) = stack.pop() {
+            filled += 1;
+            for dir in [Dir::N, Dir::S, Dir::E, Dir::W] {
+                if let Some((next_x, next_y)) = self.try_next_coord(x, y, dir) {
+                    if !*seen.get(next_x, next_y) && passable((x, y), (next_x, next_y)) {
+                        *seen.get_mut(next_x, next_y) = true;
+                        stack.push((next_x, next_y));
+                    }
+                }
+            }
+        }
+        filled
+    }
+
+    /**
+     * Every cell reachable from (x, y) by cardinal steps onto cells for which
+     * `passable` holds. The start cell is always included.
+     */
+    pub(crate) fn flood_fill<F>(&self, x: usize, y: usize, passable: F) -> Grid<bool>
+    where
+        F: Fn(&T) -> bool,
+    {
+        let mut seen = Grid::new_with(self.width, self.height, false);
+        self.fill(vec![(x, y)], &mut seen, |_, (nx, ny)| {
+            passable(self.get(nx, ny))
+        });
+        seen
+    }
+
+    /**
+     * Label the grid into 4-connected regions, where two neighbouring cells
+     * are in the same region if `same_region` holds for them. Regions are
+     * numbered in row-major order of their first cell.
+     */
+    pub(crate) fn components<F>(&self, same_region: F) -> Regions
+    where
+        F: Fn(&T, &T) -> bool,
+    {
+        let mut seen = Grid::new_with(self.width, self.height, false);
+        let mut labels = Grid::new_with(self.width, self.height, 0);
+        let mut sizes = vec![];
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if *seen.get(x, y) {
+                    continue;
+                }
+                let label = sizes.len();
+                let size = self.fill(vec![(x, y)], &mut seen, |(fx, fy), (tx, ty)| {
+                    if same_region(self.get(fx, fy), self.get(tx, ty)) {
+                        *labels.get_mut(tx, ty) = label;
+                        true
+                    } else {
+                        false
+                    }
+                });
+                *labels.get_mut(x, y) = label;
+                sizes.push(size);
+            }
+        }
+        Regions { labels, sizes }
+    }
+
+    /**
+     * Cells that are not part of the boundary and that can't reach the edge
+     * of the grid without crossing it: the regions between boundaries that
+     * don't touch the edge.
+     */
+    pub(crate) fn enclosed_by<F>(&self, is_boundary: F) -> Grid<bool>
+    where
+        F: Fn(&T) -> bool,
+    {
+        if self.width == 0 || self.height == 0 {
+            return Grid::new_with(self.width, self.height, false);
+        }
+        let regions = self.components(|a, b| !is_boundary(a) && !is_boundary(b));
+        let mut outside = vec![false; regions.count()];
+        for (x, y) in (0..self.width)
+            .flat_map(|x| [(x, 0), (x, self.height - 1)])
+            .chain((0..self.height).flat_map(|y| [(0, y), (self.width - 1, y)]))
+        {
+            outside[regions.region_of(x, y)] = true;
+        }
+        Grid::new_from(self.width, self.height, |x, y| {
+            !outside[regions.region_of(x, y)] && !is_boundary(self.get(x, y))
+        })
+    }
+}
+
+const WORD_BITS: usize = u64::BITS as usize;
+
+/**
+ * One bit per cell, packed 64 to a word with each row starting on a fresh
+ * word. Whole-grid operations (shifts, and/or, popcount, hashing) work a
+ * word at a time.
+ */
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub(crate) struct BitGrid {
+    words: Box<[u64]>,
+    words_per_row: usize,
+    pub(crate) width: usize,
+    pub(crate) height: usize,
+}
+
+impl BitGrid {
+    pub(crate) fn new(width: usize, height: usize) -> Self {
+        let words_per_row = width.div_ceil(WORD_BITS);
+        Self {
+            words: vec![0; words_per_row * height].into_boxed_slice(),
+            words_per_row,
+            width,
+            height,
+        }
+    }
+
+    pub(crate) fn new_from<F: Fn(usize, usize) -> bool>(width: usize, height: usize, f: F) -> Self {
+        let mut g = Self::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                if f(x, y) {
+                    g.set(x, y, true);
+                }
+            }
+        }
+        g
+    }
+
+    fn word_and_mask(&self, x: usize, y: usize) -> (usize, u64) {
+        assert!(x < self.width && y < self.height);
+        (y * self.words_per_row + x / WORD_BITS, 1 << (x % WORD_BITS))
+    }
+
+    pub(crate) fn get(&self, x: usize, y: usize) -> bool {
+        let (w, mask) = self.word_and_mask(x, y);
+        self.words[w] & mask != 0
+    }
+
+    pub(crate) fn set(&mut self, x: usize, y: usize, val: bool) {
+        let (w, mask) = self.word_and_mask(x, y);
+        if val {
+            self.words[w] |= mask;
+        } else {
+            self.words[w] &= !mask;
+        }
+    }
+
+    pub(crate) fn count_ones(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    #[allow(dead_code)]
+    pub(crate) fn iter_ones(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        (0..self.height)
+            .flat_map(move |y| (0..self.width).map(move |x| (x, y)))
+            .filter(|&(x, y)| self.get(x, y))
+    }
+
+    fn row(&self, y: usize) -> &[u64] {
+        &self.words[y * self.words_per_row..(y + 1) * self.words_per_row]
+    }
+
+    fn row_mut(&mut self, y: usize) -> &mut [u64] {
+        &mut self.words[y * self.words_per_row..(y + 1) * self.words_per_row]
+    }
+
+    /**
+     * Mask of the bits in the last word of each row that are inside the grid
+     */
+    fn last_word_mask(&self) -> u64 {
+        match self.width % WORD_BITS {
+            0 => u64::MAX,
+            bits => (1 << bits) - 1,
+        }
+    }
+
+    /**
+     * Every set cell moved one step in `dir`; cells that would leave the
+     * grid are dropped.
+     */
+    pub(crate) fn shifted(&self, dir: Dir) -> BitGrid {
+        let mut out = BitGrid::new(self.width, self.height);
+        if self.width == 0 || self.height == 0 {
+            return out;
+        }
         match dir {
-            Dir::W => (x.saturating_sub(max)..=(x.saturating_sub(min)))
-                .map(|c| (c, y))
-                .collect(),
-            Dir::E => ((x + min)..(x + max)).map(|c| (c, y)).collect(),
-            Dir::N => (y.saturating_sub(max)..=(y.saturating_sub(min)))
-                .map(|c| (x, c))
-                .collect(),
-            Dir::S => ((y + min)..(y + max)).map(|c| (x, c)).collect(),
+            Dir::N => out.words[..self.words.len() - self.words_per_row]
+                .copy_from_slice(&self.words[self.words_per_row..]),
+            Dir::S => out.words[self.words_per_row..]
+                .copy_from_slice(&self.words[..self.words.len() - self.words_per_row]),
+            Dir::E => {
+                let last_mask = self.last_word_mask();
+                for y in 0..self.height {
+                    let mut carry = 0;
+                    let src = self.row(y).to_vec();
+                    let dst = out.row_mut(y);
+                    for (i, w) in src.into_iter().enumerate() {
+                        dst[i] = (w << 1) | carry;
+                        carry = w >> (WORD_BITS - 1);
+                    }
+                    *dst.last_mut().unwrap() &= last_mask;
+                }
+            }
+            Dir::W => {
+                for y in 0..self.height {
+                    let mut carry = 0;
+                    let src = self.row(y).to_vec();
+                    let dst = out.row_mut(y);
+                    for (i, w) in src.into_iter().enumerate().rev() {
+                        dst[i] = (w >> 1) | carry;
+                        carry = w << (WORD_BITS - 1);
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    /**
+     * Cells that are one cardinal step away from some set cell
+     */
+    pub(crate) fn spread(&self) -> BitGrid {
+        let mut out = self.shifted(Dir::N);
+        out |= &self.shifted(Dir::S);
+        out |= &self.shifted(Dir::E);
+        out |= &self.shifted(Dir::W);
+        out
+    }
+}
+
+impl BitAndAssign<&BitGrid> for BitGrid {
+    fn bitand_assign(&mut self, rhs: &BitGrid) {
+        assert!(self.width == rhs.width && self.height == rhs.height);
+        for (w, r) in self.words.iter_mut().zip(rhs.words.iter()) {
+            *w &= r;
+        }
+    }
+}
+
+impl BitOrAssign<&BitGrid> for BitGrid {
+    fn bitor_assign(&mut self, rhs: &BitGrid) {
+        assert!(self.width == rhs.width && self.height == rhs.height);
+        for (w, r) in self.words.iter_mut().zip(rhs.words.iter()) {
+            *w |= r;
+        }
+    }
+}
+
+impl Not for &BitGrid {
+    type Output = BitGrid;
+
+    fn not(self) -> Self::Output {
+        let mut out = self.clone();
+        let last_mask = self.last_word_mask();
+        for y in 0..out.height {
+            let row = out.row_mut(y);
+            for w in row.iter_mut() {
+                *w = !*w;
+            }
+            *row.last_mut().unwrap() &= last_mask;
+        }
+        out
+    }
+}
+
+impl Display for BitGrid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut s = format!("BitGrid ({} by {}):\n", self.width, self.height);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                s.push(if self.get(x, y) { '#' } else { '.' });
+            }
+            s.push('\n');
+        }
+        write!(f, "{}", s)
+    }
+}
+
+pub(crate) type Coord3 = (usize, usize, usize);
+
+/**
+ * Every coordinate in the axis-aligned box between `lo` and `hi` inclusive,
+ * x varying fastest, then y, then z.
+ */
+pub(crate) fn box_coords(lo: Coord3, hi: Coord3) -> impl Iterator<Item = Coord3> {
+    (lo.2..=hi.2)
+        .flat_map(move |z| (lo.1..=hi.1).flat_map(move |y| (lo.0..=hi.0).map(move |x| (x, y, z))))
+}
+
+/**
+ * A dense 3-D grid stored one z layer after another, each layer laid out
+ * like a `Grid`. Accessors return `None` outside the bounds.
+ */
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub(crate) struct Grid3<T> {
+    data: Box<[T]>,
+    pub(crate) x_size: usize,
+    pub(crate) y_size: usize,
+    pub(crate) z_size: usize,
+}
+
+impl<T: Clone> Grid3<T> {
+    pub(crate) fn new_with(x_size: usize, y_size: usize, z_size: usize, el: T) -> Self {
+        Self {
+            data: vec![el; x_size * y_size * z_size].into_boxed_slice(),
+            x_size,
+            y_size,
+            z_size,
+        }
+    }
+
+    /**
+     * A copy of the z layer as a 2-D grid, x across and y down
+     */
+    #[allow(dead_code)]
+    pub(crate) fn layer(&self, z: usize) -> Option<Grid<T>> {
+        if z >= self.z_size {
+            return None;
         }
+        let layer_size = self.x_size * self.y_size;
+        let cells = self.data[z * layer_size..(z + 1) * layer_size].to_vec();
+        Some(Grid::new(
+            self.x_size,
+            self.y_size,
+            cells.into_boxed_slice(),
+        ))
+    }
+}
+
+impl<T> Grid3<T> {
+    pub(crate) fn contains(&self, (x, y, z): Coord3) -> bool {
+        x < self.x_size && y < self.y_size && z < self.z_size
+    }
+
+    fn idx(&self, c: Coord3) -> Option<usize> {
+        let (x, y, z) = c;
+        self.contains(c)
+            .then(|| (z * self.y_size + y) * self.x_size + x)
+    }
+
+    pub(crate) fn get(&self, c: Coord3) -> Option<&T> {
+        self.idx(c).map(|i| &self.data[i])
+    }
+
+    pub(crate) fn get_mut(&mut self, c: Coord3) -> Option<&mut T> {
+        self.idx(c).map(|i| &mut self.data[i])
+    }
+
+    /**
+     * Store `val` at `c`, handing it back if `c` is outside the grid
+     */
+    pub(crate) fn set(&mut self, c: Coord3, val: T) -> Result<(), T> {
+        match self.get_mut(c) {
+            Some(cell) => {
+                *cell = val;
+                Ok(())
+            }
+            None => Err(val),
+        }
+    }
+
+    /**
+     * The cells in the box between `lo` and `hi` inclusive, clipped to the
+     * grid, along with their coordinates.
+     */
+    #[allow(dead_code)]
+    pub(crate) fn iter_box(&self, lo: Coord3, hi: Coord3) -> impl Iterator<Item = (Coord3, &T)> {
+        let hi = (
+            usize::min(hi.0, self.x_size.saturating_sub(1)),
+            usize::min(hi.1, self.y_size.saturating_sub(1)),
+            usize::min(hi.2, self.z_size.saturating_sub(1)),
+        );
+        box_coords(lo, hi).filter_map(|c| self.get(c).map(|v| (c, v)))
+    }
+
+    /**
+     * The up to six cells that share a face with `c`
+     */
+    #[allow(dead_code)]
+    pub(crate) fn neighbors6(&self, (x, y, z): Coord3) -> impl Iterator<Item = Coord3> + '_ {
+        [
+            x.checked_sub(1).map(|x| (x, y, z)),
+            Some((x + 1, y, z)),
+            y.checked_sub(1).map(|y| (x, y, z)),
+            Some((x, y + 1, z)),
+            z.checked_sub(1).map(|z| (x, y, z)),
+            Some((x, y, z + 1)),
+        ]
+        .into_iter()
+        .flatten()
+        .filter(|&c| self.contains(c))
     }
 }
 
@@ -309,7 +995,7 @@ mod test {
         assert_eq!(
             g.cardinal_neighbors(5, 0, 0..2)
                 .iter()
-                .filter(|(x, y, d)| g.weight_between(5, 0, *x, *y) == 26)
+                .filter(|(x, y, d)| g.weight_between(5, 0, *x, *y) == Some(26))
                 .count(),
             3
         );
@@ -322,10 +1008,10 @@ mod test {
     fn test_weights() {
         let g: Grid<Test> = Grid::new_from(10, 10, |x, y| Test(2 * (x as u8) + 2 * (y as u8)));
         println!("{}", g);
-        assert_eq!(g.weight_between(0, 0, 3, 0), Test(12));
-        assert_eq!(g.weight_between(3, 0, 0, 0), Test(6));
-        assert_eq!(g.weight_between(0, 0, 0, 3), Test(12));
-        assert_eq!(g.weight_between(0, 3, 0, 0), Test(6));
+        assert_eq!(g.weight_between(0, 0, 3, 0), Some(Test(12)));
+        assert_eq!(g.weight_between(3, 0, 0, 0), Some(Test(6)));
+        assert_eq!(g.weight_between(0, 0, 0, 3), Some(Test(12)));
+        assert_eq!(g.weight_between(0, 3, 0, 0), Some(Test(6)));
     }
 
     #[test]
@@ -340,7 +1026,7 @@ mod test {
         .unwrap();
 
         assert_eq!(g.cardinal_neighbors(0, 0, 3..10).len(), 8);
-        assert_eq!(g.weight_between(0, 0, 0, 4), 36);
+        assert_eq!(g.weight_between(0, 0, 0, 4), Some(36));
     }
 
     #[test]
@@ -353,9 +1039,262 @@ mod test {
          999999999991",
         )
         .unwrap();
-        assert_eq!(g.direction_range(0, 0, 4, 11, Dir::E).len(), 7);
-        assert_eq!(g.direction_range(11, 4, 4, 11, Dir::N).len(), 1);
-        assert_eq!(g.direction_range(4, 0, 4, 11, Dir::W).len(), 1);
-        assert_eq!(g.direction_range(7, 0, 4, 11, Dir::E).len(), 1);
+        assert_eq!(g.direction_range(0, 0, 4, 11, Dir::E).unwrap().len(), 7);
+        assert_eq!(g.direction_range(11, 4, 4, 11, Dir::N).unwrap().len(), 1);
+        assert_eq!(g.direction_range(4, 0, 4, 11, Dir::W).unwrap().len(), 1);
+        assert_eq!(g.direction_range(7, 0, 4, 11, Dir::E).unwrap().len(), 1);
+        assert_eq!(g.direction_range(8, 0, 4, 11, Dir::E), None);
+        assert_eq!(g.direction_range(0, 0, 0, 1, Dir::W), Some(vec![(0, 0)]));
+    }
+
+    #[test]
+    fn test_ray() {
+        let g: Grid<u16> = Grid::from_str(
+            "123
+             456
+             789",
+        )
+        .unwrap();
+        let cells: Vec<_> = g
+            .ray(0, 1, Dir::E)
+            .unwrap()
+            .map(|(x, y, &v)| (x, y, v))
+            .collect();
+        assert_eq!(cells, vec![(1, 1, 5), (2, 1, 6)]);
+        assert_eq!(g.ray(2, 2, Dir::N).unwrap().weight(), 9);
+        assert_eq!(g.ray(0, 0, Dir::N).unwrap().count(), 0);
+        assert_eq!(g.ray(0, 0, Dir::S).unwrap().until(|&v| v > 5).count(), 1);
+
+        assert!(g.ray(4, 0, Dir::S).is_none());
+        assert!(g.ray(3, 0, Dir::W).is_none());
+        assert!(g.ray(0, 3, Dir::N).is_none());
+        assert!(g.direction_range(0, 3, 0, 2, Dir::N).is_none());
+
+        assert_eq!(g.segment(2, 0, 0, 0).unwrap().weight(), 3);
+        assert_eq!(g.segment(0, 0, 0, 1).unwrap().count(), 1);
+        assert!(g.segment(0, 0, 1, 1).is_none());
+        assert!(g.segment(1, 1, 1, 1).is_none());
+        assert!(g.segment(0, 0, 3, 0).is_none());
+        assert_eq!(g.weight_between(0, 0, 2, 2), None);
+    }
+
+    #[test]
+    fn test_sparse() {
+        let mut g = SparseGrid::new(0u32);
+        assert!(g.max() < g.min());
+        assert!(g.is_empty());
+
+        g.set(3, 1, 7);
+        assert_eq!((g.min(), g.max()), ((3, 1), (3, 1)));
+        assert_eq!(*g.at(3, 1), 7);
+        assert!(!g.contains(0, 0));
+
+        let mut view = g.view((3, 1), (5, 1));
+        assert_eq!((view.width(), view.height()), (3, 1));
+        assert_eq!(*view.get(0, 0), 7);
+        *view.get_mut(2, 0) += 2;
+        assert_eq!(view.cardinal_neighbors(0, 0, 0..1).len(), 1);
+        assert_eq!(view.try_next_coord(2, 0, Dir::E), None);
+        assert_eq!(*g.at(5, 1), 2);
+        assert_eq!(g.len(), 2);
+        assert_eq!((g.min(), g.max()), ((3, 1), (5, 1)));
+
+        assert_eq!(g.remove(3, 1), Some(7));
+        assert_eq!(*g.at(3, 1), 0);
+        assert_eq!((g.min(), g.max()), ((3, 1), (5, 1)));
+    }
+
+    #[test]
+    fn test_sparse_negative() {
+        let mut g = SparseGrid::new('.');
+        g.set(0, 0, '#');
+        g.set(-2, -1, '#');
+        g.set(1, -3, '#');
+        assert_eq!((g.min(), g.max()), ((-2, -3), (1, 0)));
+        assert_eq!(*g.at(-2, -1), '#');
+        let view = g.view(g.min(), g.max());
+        assert_eq!((view.width(), view.height()), (4, 4));
+        assert_eq!(*view.get(0, 2), '#');
+        assert_eq!(*view.get(3, 0), '#');
+        assert_eq!(*view.get(2, 3), '#');
+        assert_eq!(*view.get(1, 1), '.');
+        let mut cells: Vec<(i64, i64)> = g.iter().map(|(c, _)| c).collect();
+        cells.sort();
+        assert_eq!(cells, vec![(-2, -1), (0, 0), (1, -3)]);
+    }
+
+    #[test]
+    fn test_sparse_view_stays_put() {
+        let mut g = SparseGrid::new(0);
+        g.set(0, 0, 7);
+        let mut view = g.view((0, 0), (1, 1));
+        assert_eq!(*view.get(0, 0), 7);
+        *view.get_mut(3, 3) = 5;
+        assert_eq!(*view.get(0, 0), 7);
+        assert_eq!((view.width(), view.height()), (2, 2));
+
+        g.set(-1, -1, 9);
+        assert_eq!((g.min(), g.max()), ((-1, -1), (3, 3)));
+        assert_eq!(*g.view((0, 0), (1, 1)).get(0, 0), 7);
+        assert_eq!(*g.view(g.min(), g.max()).get(0, 0), 9);
+        assert_eq!(*g.at(3, 3), 5);
+    }
+
+    #[test]
+    fn test_sparse_matches_dense() {
+        fn count_diagonal<G: GridLike<u32>>(g: &G) -> usize {
+            (0..g.height())
+                .flat_map(|y| (0..g.width()).map(move |x| (x, y)))
+                .filter(|&(x, y)| *g.get(x, y) == 1)
+                .count()
+        }
+
+        let dense = Grid::new_from(4, 3, |x, y| if x == y { 1u32 } else { 0 });
+        let mut sparse = SparseGrid::with_size(4, 3, 0u32);
+        for i in 0..3 {
+            sparse.set(i, i, 1);
+        }
+        assert_eq!(dense.to_string(), sparse.to_string());
+        assert_eq!(count_diagonal(&dense), 3);
+        assert_eq!(count_diagonal(&sparse.view((0, 0), (3, 2))), 3);
+        assert_eq!(sparse.iter().count(), 3);
+    }
+
+    fn char_grid(rows: &[&str]) -> Grid<u8> {
+        Grid::new_from(rows[0].len(), rows.len(), |x, y| rows[y].as_bytes()[x])
+    }
+
+    #[test]
+    fn test_flood_fill() {
+        let g = char_grid(&["..#..", "..#..", "###..", "....."]);
+        let filled = g.flood_fill(0, 0, |&c| c == b'.');
+        assert_eq!(filled.count_cells(|&b| b), 4);
+        assert!(!*filled.get(3, 0));
+
+        let filled = g.flood_fill(4, 0, |&c| c == b'.');
+        assert_eq!(filled.count_cells(|&b| b), 11);
+        assert!(*filled.get(0, 3));
+    }
+
+    #[test]
+    fn test_components() {
+        let g = char_grid(&["aab", "abb", "cca"]);
+        let regions = g.components(|a, b| a == b);
+        assert_eq!(regions.count(), 4);
+        assert_eq!(regions.sizes, vec![3, 3, 2, 1]);
+        assert_eq!(regions.region_of(0, 0), regions.region_of(0, 1));
+        assert_ne!(regions.region_of(0, 0), regions.region_of(2, 2));
+        assert_eq!(regions.sizes.iter().sum::<usize>(), g.width * g.height);
+    }
+
+    #[test]
+    fn test_enclosed() {
+        let g = char_grid(&[
+            ".......", ".#####.", ".#..#..", ".#..#..", ".####..", ".......",
+        ]);
+        let inside = g.enclosed_by(|&c| c == b'#');
+        assert_eq!(inside.count_cells(|&b| b), 4);
+        assert!(*inside.get(2, 2));
+        assert!(!*inside.get(1, 1));
+        assert!(!*inside.get(5, 2));
+
+        // a gap in the boundary lets the outside in
+        let g = char_grid(&["#####", "#...#", "#....", "#####"]);
+        assert_eq!(g.enclosed_by(|&c| c == b'#').count_cells(|&b| b), 0);
+
+        let empty: Grid<u8> = Grid::new_with(0, 3, b'#');
+        let inside = empty.enclosed_by(|&c| c == b'#');
+        assert_eq!((inside.width, inside.height), (0, 3));
+        let empty: Grid<u8> = Grid::new_with(4, 0, b'#');
+        assert_eq!(empty.enclosed_by(|&c| c == b'#').count_cells(|&b| b), 0);
+    }
+
+    #[test]
+    fn test_bitgrid() {
+        let mut g = BitGrid::new(70, 3);
+        assert_eq!(g.count_ones(), 0);
+        g.set(0, 0, true);
+        g.set(63, 1, true);
+        g.set(69, 2, true);
+        assert!(g.get(63, 1));
+        assert!(!g.get(64, 1));
+        assert_eq!(g.count_ones(), 3);
+        assert_eq!(
+            g.iter_ones().collect::<Vec<_>>(),
+            vec![(0, 0), (63, 1), (69, 2)]
+        );
+
+        // crossing a word boundary, and falling off the edges
+        let e = g.shifted(Dir::E);
+        assert_eq!(e.iter_ones().collect::<Vec<_>>(), vec![(1, 0), (64, 1)]);
+        let w = e.shifted(Dir::W);
+        assert_eq!(w.iter_ones().collect::<Vec<_>>(), vec![(0, 0), (63, 1)]);
+        assert_eq!(w.shifted(Dir::W).count_ones(), 1);
+        let s = g.shifted(Dir::S);
+        assert_eq!(s.iter_ones().collect::<Vec<_>>(), vec![(0, 1), (63, 2)]);
+        assert_eq!(s.shifted(Dir::N), {
+            let mut n = g.clone();
+            n.set(69, 2, false);
+            n
+        });
+
+        let inv = !&g;
+        assert_eq!(inv.count_ones(), 70 * 3 - 3);
+        let mut both = inv.clone();
+        both &= &g;
+        assert_eq!(both.count_ones(), 0);
+        both |= &g;
+        assert_eq!(both, g);
+    }
+
+    #[test]
+    fn test_bitgrid_spread() {
+        let open = BitGrid::new_from(5, 5, |x, y| !(x == 2 && y == 1));
+        let mut reached = BitGrid::new(5, 5);
+        reached.set(2, 2, true);
+        reached = reached.spread();
+        reached &= &open;
+        assert_eq!(reached.count_ones(), 3);
+        reached = reached.spread();
+        reached &= &open;
+        assert_eq!(
+            reached.to_string(),
+            "BitGrid (5 by 5):\n.....\n.#.#.\n#.#.#\n.#.#.\n..#..\n"
+        );
+    }
+
+    #[test]
+    fn test_box_coords() {
+        let v: Vec<Coord3> = box_coords((1, 0, 5), (2, 1, 5)).collect();
+        assert_eq!(v, vec![(1, 0, 5), (2, 0, 5), (1, 1, 5), (2, 1, 5)]);
+        assert_eq!(box_coords((3, 3, 3), (3, 3, 3)).count(), 1);
+        assert_eq!(box_coords((3, 3, 3), (2, 3, 3)).count(), 0);
+    }
+
+    #[test]
+    fn test_grid3() {
+        let mut g = Grid3::new_with(3, 4, 5, 0u16);
+        assert_eq!(g.set((2, 3, 4), 7), Ok(()));
+        assert_eq!(g.set((3, 0, 0), 9), Err(9));
+        assert_eq!(g.get((2, 3, 4)), Some(&7));
+        assert_eq!(g.get((0, 4, 0)), None);
+        *g.get_mut((1, 1, 1)).unwrap() = 3;
+
+        let layer = g.layer(4).unwrap();
+        assert_eq!((layer.width, layer.height), (3, 4));
+        assert_eq!(*layer.get(2, 3), 7);
+        assert!(g.layer(5).is_none());
+
+        assert_eq!(g.neighbors6((0, 0, 0)).count(), 3);
+        assert_eq!(g.neighbors6((1, 1, 1)).count(), 6);
+        assert_eq!(g.neighbors6((2, 3, 4)).count(), 3);
+
+        let set: Vec<Coord3> = g
+            .iter_box((0, 0, 0), (10, 10, 10))
+            .filter(|(_, &v)| v != 0)
+            .map(|(c, _)| c)
+            .collect();
+        assert_eq!(set, vec![(1, 1, 1), (2, 3, 4)]);
+        assert_eq!(g.iter_box((1, 1, 1), (2, 2, 2)).count(), 8);
     }
 }
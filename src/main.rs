@@ -1,7 +1,16 @@
 use clap::{Parser, Subcommand};
-use std::{fmt::Display, path::Path};
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+};
 
+mod cycle;
+#[cfg(feature = "serde")]
+mod dump;
 mod grid;
+mod record;
+mod render;
+mod search;
 
 mod d1;
 mod d10;
@@ -33,6 +42,17 @@ pub trait PuzzleRun {
     fn input_data(&self) -> anyhow::Result<&str>;
 
     fn run(&self, input: &str) -> String;
+
+    /// Write the model that `run` parses its input into
+    #[cfg(feature = "serde")]
+    fn dump_parsed(
+        &self,
+        _input: &str,
+        _out: &mut dyn std::io::Write,
+        _format: dump::DumpFormat,
+    ) -> anyhow::Result<()> {
+        anyhow::bail!("this puzzle has no parsed model to dump")
+    }
 }
 
 fn read_file<P: AsRef<Path>>(filename: P) -> anyhow::Result<&'static str> {
@@ -46,29 +66,108 @@ struct Args {
     #[arg(short, long, action = clap::ArgAction::Count)]
     debug: u8,
 
+    /// Record each step of the simulation days
+    #[arg(long, value_enum)]
+    record: Option<record::RecordFormat>,
+
+    /// Write the parsed puzzle input to this file (.json or .bin) instead of
+    /// solving. Needs a day; a day with several runs writes one file per run,
+    /// numbered like `day5-1.json`.
+    #[arg(long, value_name = "FILE")]
+    dump_parsed: Option<PathBuf>,
+
     /// Which day's puzzles to run?
     #[command(subcommand)]
     day: Option<PuzzleDay>,
 }
 #[derive(Subcommand, Debug)]
 enum PuzzleDay {
-    Day1,
-    Day2,
-    Day3,
+    Day1 {
+        /// Stream calibration values from this file ("-" for stdin) instead
+        /// of solving
+        #[arg(long, value_name = "FILE")]
+        stream: Option<PathBuf>,
+
+        /// Which words count as digits when streaming
+        #[arg(long, value_enum, default_value = "english")]
+        language: d1::Language,
+
+        /// Also print the words matched on each line and where
+        #[arg(long)]
+        explain: bool,
+    },
+    Day2 {
+        /// Say why each impossible game can't come from the bag instead of
+        /// solving
+        #[arg(long)]
+        explain: bool,
+    },
+    Day3 {
+        /// List the numbers next to each symbol and those next to none
+        /// instead of solving
+        #[arg(long)]
+        report: bool,
+
+        /// Only count these characters as symbols in the report
+        #[arg(long, value_name = "CHARS", conflicts_with = "except")]
+        only: Option<String>,
+
+        /// Count everything but digits, `.` and these characters as symbols
+        /// in the report
+        #[arg(long, value_name = "CHARS")]
+        except: Option<String>,
+    },
     Day4,
-    Day5,
+    Day5 {
+        /// Print the seed-to-location maps composed into one table instead
+        /// of solving
+        #[arg(long)]
+        table: bool,
+    },
     Day6,
     Day7,
-    Day8,
+    Day8 {
+        /// Write the network as Graphviz DOT to this file instead of solving
+        #[arg(long, value_name = "FILE")]
+        dot: Option<PathBuf>,
+
+        /// Check the network and list unreachable nodes, sinks and what each
+        /// start can reach instead of solving
+        #[arg(long)]
+        analyse: bool,
+    },
     Day9,
-    Day10,
+    Day10 {
+        /// Draw the maze in colour instead of solving
+        #[arg(long)]
+        draw: bool,
+
+        /// Write the drawing as plain text to this file instead of solving
+        #[arg(long, value_name = "FILE")]
+        text: Option<PathBuf>,
+
+        /// Save the main loop as an image (.png, .ppm or .svg) instead of
+        /// solving
+        #[arg(long, value_name = "FILE")]
+        image: Option<PathBuf>,
+    },
     Day11,
     Day12,
     Day13,
     Day14,
     Day15,
-    Day16,
-    Day17,
+    Day16 {
+        /// Save the beam from the top left as an image (.png, .ppm or .svg)
+        /// instead of solving
+        #[arg(long, value_name = "FILE")]
+        image: Option<PathBuf>,
+    },
+    Day17 {
+        /// Save the ultra crucible's route as an image (.png, .ppm or .svg)
+        /// instead of solving
+        #[arg(long, value_name = "FILE")]
+        image: Option<PathBuf>,
+    },
     Day18,
     Day19,
     Day20,
@@ -80,25 +179,55 @@ fn main() -> anyhow::Result<()> {
     tracing_subscriber::fmt::init();
 
     let args = Args::parse();
+    if let Some(format) = args.record {
+        record::record_as(format);
+    }
+
+    if args.dump_parsed.is_some() && args.day.is_none() {
+        anyhow::bail!("--dump-parsed needs a day");
+    }
 
     let puzzles = match args.day {
-        Some(PuzzleDay::Day1) => d1::get_runs(),
-        Some(PuzzleDay::Day2) => d2::get_runs(),
-        Some(PuzzleDay::Day3) => d3::get_runs(),
+        Some(PuzzleDay::Day1 {
+            stream: Some(path),
+            language,
+            explain,
+        }) => return d1::stream_file(&path, language, explain),
+        Some(PuzzleDay::Day1 { .. }) => d1::get_runs(),
+        Some(PuzzleDay::Day2 { explain: true }) => return d2::explain(),
+        Some(PuzzleDay::Day2 { .. }) => d2::get_runs(),
+        Some(PuzzleDay::Day3 {
+            report: true,
+            only,
+            except,
+        }) => return d3::report(only.as_deref(), except.as_deref()),
+        Some(PuzzleDay::Day3 { .. }) => d3::get_runs(),
         Some(PuzzleDay::Day4) => d4::get_runs(),
-        Some(PuzzleDay::Day5) => d5::get_runs(),
+        Some(PuzzleDay::Day5 { table: true }) => return d5::print_table(),
+        Some(PuzzleDay::Day5 { .. }) => d5::get_runs(),
         Some(PuzzleDay::Day6) => d6::get_runs(),
         Some(PuzzleDay::Day7) => d7::get_runs(),
-        Some(PuzzleDay::Day8) => d8::get_runs(),
+        Some(PuzzleDay::Day8 { dot, analyse }) if dot.is_some() || analyse => {
+            return d8::analyse(dot.as_deref(), analyse)
+        }
+        Some(PuzzleDay::Day8 { .. }) => d8::get_runs(),
         Some(PuzzleDay::Day9) => d9::get_runs(),
-        Some(PuzzleDay::Day10) => d10::get_runs(),
+        Some(PuzzleDay::Day10 {
+            image: Some(path), ..
+        }) => return d10::render(&path),
+        Some(PuzzleDay::Day10 { draw, text, .. }) if draw || text.is_some() => {
+            return d10::draw(draw, text.as_deref())
+        }
+        Some(PuzzleDay::Day10 { .. }) => d10::get_runs(),
         Some(PuzzleDay::Day11) => d11::get_runs(),
         Some(PuzzleDay::Day12) => d12::get_runs(),
         Some(PuzzleDay::Day13) => d13::get_runs(),
         Some(PuzzleDay::Day14) => d14::get_runs(),
         Some(PuzzleDay::Day15) => d15::get_runs(),
-        Some(PuzzleDay::Day16) => d16::get_runs(),
-        Some(PuzzleDay::Day17) => d17::get_runs(),
+        Some(PuzzleDay::Day16 { image: Some(path) }) => return d16::render(&path),
+        Some(PuzzleDay::Day16 { .. }) => d16::get_runs(),
+        Some(PuzzleDay::Day17 { image: Some(path) }) => return d17::render(&path),
+        Some(PuzzleDay::Day17 { .. }) => d17::get_runs(),
         Some(PuzzleDay::Day18) => d18::get_runs(),
         Some(PuzzleDay::Day19) => d19::get_runs(),
         Some(PuzzleDay::Day20) => d20::get_runs(),
@@ -110,8 +239,31 @@ fn main() -> anyhow::Result<()> {
         }
     };
 
+    if let Some(path) = args.dump_parsed {
+        return dump_parsed(&puzzles, &path);
+    }
+
     for puzzle in puzzles {
         println!("{}", puzzle.run(puzzle.input_data()?));
     }
     Ok(())
 }
+
+#[cfg(feature = "serde")]
+fn dump_parsed(puzzles: &[Box<dyn PuzzleRun>], path: &Path) -> anyhow::Result<()> {
+    use std::io::Write;
+
+    let format = dump::DumpFormat::for_path(path)?;
+    for (i, puzzle) in puzzles.iter().enumerate() {
+        let path = dump::path_for_run(path, i, puzzles.len());
+        let mut out = std::io::BufWriter::new(std::fs::File::create(&path)?);
+        puzzle.dump_parsed(puzzle.input_data()?, &mut out, format)?;
+        out.flush()?;
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "serde"))]
+fn dump_parsed(_puzzles: &[Box<dyn PuzzleRun>], _path: &Path) -> anyhow::Result<()> {
+    anyhow::bail!("--dump-parsed needs the `serde` feature")
+}
@@ -0,0 +1,280 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::PathBuf,
+    sync::OnceLock,
+    thread,
+    time::Duration,
+};
+
+use clap::ValueEnum;
+
+use crate::render::Image;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum RecordFormat {
+    /// One numbered PNG per step
+    Frames,
+    Gif,
+    Apng,
+    /// Replay the steps in the terminal
+    Ansi,
+}
+
+static RECORD_AS: OnceLock<RecordFormat> = OnceLock::new();
+
+/**
+ * Turn on recording for every `Recorder::new` from here on
+ */
+pub(crate) fn record_as(format: RecordFormat) {
+    RECORD_AS.set(format).expect("recording format already set");
+}
+
+/**
+ * Collects a frame for each step of a simulation and writes them all out
+ * when finished. When recording is off, `frame` never calls its closure, so
+ * simulations can call it every step without paying for images.
+ */
+pub(crate) struct Recorder {
+    name: String,
+    format: Option<RecordFormat>,
+    dir: PathBuf,
+    delay: Duration,
+    frames: Vec<Image>,
+}
+
+impl Recorder {
+    /**
+     * Records in whatever format was picked on the command line, if any.
+     * Output goes in `frames/`, named after `name`.
+     */
+    pub(crate) fn new(name: &str) -> Self {
+        Self::with_format(name, RECORD_AS.get().copied())
+    }
+
+    pub(crate) fn off() -> Self {
+        Self::with_format("", None)
+    }
+
+    pub(crate) fn with_format(name: &str, format: Option<RecordFormat>) -> Self {
+        Self {
+            name: name.to_string(),
+            format,
+            dir: PathBuf::from("frames"),
+            delay: Duration::from_millis(100),
+            frames: vec![],
+        }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.dir = dir.into();
+        self
+    }
+
+    #[allow(dead_code)]
+    pub(crate) fn delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    pub(crate) fn is_on(&self) -> bool {
+        self.format.is_some()
+    }
+
+    pub(crate) fn frame<F: FnOnce() -> Image>(&mut self, image: F) {
+        if self.is_on() {
+            self.frames.push(image());
+        }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    #[cfg(test)]
+    pub(crate) fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    pub(crate) fn finish(self) -> anyhow::Result<()> {
+        let Some(format) = self.format else {
+            return Ok(());
+        };
+        if self.frames.is_empty() {
+            return Ok(());
+        }
+        let (width, height) = (self.frames[0].pixel_width(), self.frames[0].pixel_height());
+        if self
+            .frames
+            .iter()
+            .any(|f| (f.pixel_width(), f.pixel_height()) != (width, height))
+        {
+            anyhow::bail!("{}: frames are not all the same size", self.name);
+        }
+
+        match format {
+            RecordFormat::Frames => {
+                let dir = self.dir.join(&self.name);
+                fs::create_dir_all(&dir)?;
+                for (i, frame) in self.frames.iter().enumerate() {
+                    frame.save(dir.join(format!("{:05}.png", i)))?;
+                }
+            }
+            RecordFormat::Gif => {
+                fs::create_dir_all(&self.dir)?;
+                let out =
+                    BufWriter::new(File::create(self.dir.join(format!("{}.gif", self.name)))?);
+                self.write_gif(out)?;
+            }
+            RecordFormat::Apng => {
+                fs::create_dir_all(&self.dir)?;
+                let out =
+                    BufWriter::new(File::create(self.dir.join(format!("{}.png", self.name)))?);
+                self.write_apng(out)?;
+            }
+            RecordFormat::Ansi => {
+                let mut out = io::stdout().lock();
+                for frame in &self.frames {
+                    write!(out, "\x1b[2J\x1b[H{}", ansi(frame))?;
+                    out.flush()?;
+                    thread::sleep(self.delay);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn write_gif<W: Write>(&self, out: W) -> anyhow::Result<()> {
+        let (width, height) = (self.frames[0].pixel_width(), self.frames[0].pixel_height());
+        let mut encoder = gif::Encoder::new(out, width.try_into()?, height.try_into()?, &[])?;
+        encoder.set_repeat(gif::Repeat::Infinite)?;
+        for image in &self.frames {
+            let mut frame = gif::Frame::from_rgb_speed(
+                width as u16,
+                height as u16,
+                &image.pixels().concat(),
+                10,
+            );
+            frame.delay = (self.delay.as_millis() / 10).try_into()?;
+            encoder.write_frame(&frame)?;
+        }
+        Ok(())
+    }
+
+    fn write_apng<W: Write>(&self, out: W) -> anyhow::Result<()> {
+        let (width, height) = (self.frames[0].pixel_width(), self.frames[0].pixel_height());
+        let mut encoder = png::Encoder::new(out, width.try_into()?, height.try_into()?);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_animated(self.frames.len().try_into()?, 0)?;
+        encoder.set_frame_delay(self.delay.as_millis().try_into()?, 1000)?;
+        let mut writer = encoder.write_header()?;
+        for image in &self.frames {
+            writer.write_image_data(&image.pixels().concat())?;
+        }
+        writer.finish()?;
+        Ok(())
+    }
+}
+
+/**
+ * One cell per pixel whatever the image's scale, two rows per line of text:
+ * a half block with the top cell as the foreground colour and the bottom cell
+ * as the background
+ */
+fn ansi(image: &Image) -> String {
+    let image = image.clone().scale(1);
+    let (width, height) = (image.pixel_width(), image.pixel_height());
+    let pixels = image.pixels();
+    let mut s = String::new();
+    for y in (0..height).step_by(2) {
+        for x in 0..width {
+            let [r, g, b] = pixels[y * width + x];
+            s.push_str(&format!("\x1b[38;2;{};{};{}m", r, g, b));
+            if y + 1 < height {
+                let [r, g, b] = pixels[(y + 1) * width + x];
+                s.push_str(&format!("\x1b[48;2;{};{};{}m", r, g, b));
+            } else {
+                s.push_str("\x1b[49m");
+            }
+            s.push('▀');
+        }
+        s.push_str("\x1b[0m\n");
+    }
+    s
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::render::{BLACK, WHITE};
+
+    fn frame(n: usize) -> Image {
+        Image::from_fn(4, 3, |x, y| if x + y <= n { WHITE } else { BLACK })
+    }
+
+    #[test]
+    fn test_off() {
+        let mut recorder = Recorder::off();
+        recorder.frame(|| panic!("should not render when off"));
+        assert!(recorder.is_empty());
+        recorder.finish().unwrap();
+    }
+
+    #[test]
+    fn test_ansi() {
+        let s = ansi(&frame(0));
+        assert_eq!(s.lines().count(), 2);
+        assert_eq!(s.matches('▀').count(), 8);
+        assert!(s.starts_with("\x1b[38;2;255;255;255m\x1b[48;2;0;0;0m▀"));
+        // odd height leaves the last line's background alone
+        assert!(s.lines().nth(1).unwrap().contains("\x1b[49m"));
+    }
+
+    #[test]
+    fn test_gif_and_apng() {
+        let dir = std::env::temp_dir().join("record_test");
+        for format in [RecordFormat::Gif, RecordFormat::Apng, RecordFormat::Frames] {
+            let mut recorder = Recorder::with_format("steps", Some(format)).dir(&dir);
+            for n in 0..5 {
+                recorder.frame(|| frame(n));
+            }
+            assert_eq!(recorder.len(), 5);
+            recorder.finish().unwrap();
+        }
+
+        let mut reader = png::Decoder::new(File::open(dir.join("steps.png")).unwrap())
+            .read_info()
+            .unwrap();
+        assert_eq!(reader.info().animation_control().unwrap().num_frames, 5);
+        let mut buf = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut buf).unwrap();
+        assert_eq!(&buf[..4 * 3 * 3], &frame(0).pixels().concat()[..]);
+
+        let mut decoder = gif::DecodeOptions::new();
+        decoder.set_color_output(gif::ColorOutput::RGBA);
+        let mut decoder = decoder
+            .read_info(File::open(dir.join("steps.gif")).unwrap())
+            .unwrap();
+        let mut frames = 0;
+        while let Some(f) = decoder.read_next_frame().unwrap() {
+            assert_eq!((f.width, f.height), (4, 3));
+            frames += 1;
+        }
+        assert_eq!(frames, 5);
+
+        assert!(dir.join("steps").join("00004.png").exists());
+    }
+
+    #[test]
+    fn test_mismatched_frames() {
+        let mut recorder = Recorder::with_format("bad", Some(RecordFormat::Gif))
+            .dir(std::env::temp_dir().join("record_test"));
+        recorder.frame(|| frame(0));
+        recorder.frame(|| frame(0).scale(2));
+        assert!(recorder.finish().is_err());
+    }
+}
@@ -0,0 +1,339 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use crate::grid::Grid;
+
+pub(crate) type Rgb = [u8; 3];
+
+pub(crate) const BLACK: Rgb = [0, 0, 0];
+pub(crate) const WHITE: Rgb = [255, 255, 255];
+pub(crate) const RED: Rgb = [220, 40, 40];
+pub(crate) const YELLOW: Rgb = [250, 210, 40];
+pub(crate) const GREY: Rgb = [128, 128, 128];
+
+/**
+ * A distinct colour for each small integer, for telling ids apart
+ */
+pub(crate) fn palette(i: usize) -> Rgb {
+    // step round the hue circle by the golden ratio so neighbours differ
+    let hue = (i as f64 * 0.618_034).fract() * 6.0;
+    let x = 1.0 - (hue % 2.0 - 1.0).abs();
+    let (r, g, b) = match hue as usize {
+        0 => (1.0, x, 0.0),
+        1 => (x, 1.0, 0.0),
+        2 => (0.0, 1.0, x),
+        3 => (0.0, x, 1.0),
+        4 => (x, 0.0, 1.0),
+        _ => (1.0, 0.0, x),
+    };
+    [r, g, b].map(|c: f64| (55.0 + c * 200.0) as u8)
+}
+
+/**
+ * A grid turned into one colour per cell, ready to write out as an image.
+ * Each cell is drawn as a `scale` by `scale` square, and any paths are drawn
+ * on top as lines through the centres of their cells.
+ */
+#[derive(Debug, Clone)]
+pub(crate) struct Image {
+    width: usize,
+    height: usize,
+    cells: Vec<Rgb>,
+    scale: usize,
+    paths: Vec<(Vec<(usize, usize)>, Rgb)>,
+}
+
+impl Image {
+    pub(crate) fn new<T, F: Fn(&T) -> Rgb>(grid: &Grid<T>, colour: F) -> Self {
+        Self::from_fn(grid.width, grid.height, |x, y| colour(grid.get(x, y)))
+    }
+
+    /**
+     * For anything grid shaped that isn't a `Grid`, colouring cell (x, y)
+     * with `colour(x, y)`
+     */
+    pub(crate) fn from_fn<F: Fn(usize, usize) -> Rgb>(
+        width: usize,
+        height: usize,
+        colour: F,
+    ) -> Self {
+        let mut cells = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                cells.push(colour(x, y));
+            }
+        }
+        Self {
+            width,
+            height,
+            cells,
+            scale: 1,
+            paths: vec![],
+        }
+    }
+
+    pub(crate) fn scale(mut self, scale: usize) -> Self {
+        assert!(scale > 0);
+        self.scale = scale;
+        self
+    }
+
+    pub(crate) fn path<I: IntoIterator<Item = (usize, usize)>>(
+        mut self,
+        path: I,
+        colour: Rgb,
+    ) -> Self {
+        self.paths.push((path.into_iter().collect(), colour));
+        self
+    }
+
+    pub(crate) fn pixel_width(&self) -> usize {
+        self.width * self.scale
+    }
+
+    pub(crate) fn pixel_height(&self) -> usize {
+        self.height * self.scale
+    }
+
+    /**
+     * Every pixel in row-major order, with the paths drawn in
+     */
+    pub(crate) fn pixels(&self) -> Vec<Rgb> {
+        let (w, h, s) = (self.pixel_width(), self.pixel_height(), self.scale);
+        let mut pixels = Vec::with_capacity(w * h);
+        for py in 0..h {
+            for px in 0..w {
+                pixels.push(self.cells[(py / s) * self.width + px / s]);
+            }
+        }
+
+        let thickness = usize::max(1, s / 4);
+        let centre = |(x, y): (usize, usize)| ((x * s + s / 2) as i64, (y * s + s / 2) as i64);
+        for (path, colour) in &self.paths {
+            let mut plot = |cx: i64, cy: i64| {
+                let lo = thickness as i64 / 2;
+                for py in cy - lo..cy - lo + thickness as i64 {
+                    for px in cx - lo..cx - lo + thickness as i64 {
+                        if (0..w as i64).contains(&px) && (0..h as i64).contains(&py) {
+                            pixels[py as usize * w + px as usize] = *colour;
+                        }
+                    }
+                }
+            };
+            if let [only] = path[..] {
+                let (x, y) = centre(only);
+                plot(x, y);
+            }
+            for pair in path.windows(2) {
+                line(centre(pair[0]), centre(pair[1]), &mut plot);
+            }
+        }
+        pixels
+    }
+
+    pub(crate) fn write_ppm<W: Write>(&self, mut out: W) -> io::Result<()> {
+        write!(
+            out,
+            "P6\n{} {}\n255\n",
+            self.pixel_width(),
+            self.pixel_height()
+        )?;
+        out.write_all(&self.pixels().concat())
+    }
+
+    pub(crate) fn write_png<W: Write>(&self, out: W) -> anyhow::Result<()> {
+        let mut encoder = png::Encoder::new(
+            out,
+            self.pixel_width().try_into()?,
+            self.pixel_height().try_into()?,
+        );
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.pixels().concat())?;
+        Ok(())
+    }
+
+    /**
+     * One rectangle per run of same-coloured cells in a row, in cell units,
+     * so the picture stays sharp at any zoom
+     */
+    pub(crate) fn write_svg<W: Write>(&self, mut out: W) -> io::Result<()> {
+        writeln!(
+            out,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" viewBox="0 0 {} {}" shape-rendering="crispEdges">"#,
+            self.pixel_width(),
+            self.pixel_height(),
+            self.width,
+            self.height
+        )?;
+        for y in 0..self.height {
+            let row = &self.cells[y * self.width..(y + 1) * self.width];
+            let mut x = 0;
+            while x < self.width {
+                let run = row[x..].iter().take_while(|&&c| c == row[x]).count();
+                writeln!(
+                    out,
+                    r#"<rect x="{}" y="{}" width="{}" height="1" fill="{}"/>"#,
+                    x,
+                    y,
+                    run,
+                    hex(row[x])
+                )?;
+                x += run;
+            }
+        }
+        for (path, colour) in &self.paths {
+            let points: Vec<String> = path
+                .iter()
+                .map(|(x, y)| format!("{}.5,{}.5", x, y))
+                .collect();
+            writeln!(
+                out,
+                r#"<polyline points="{}" fill="none" stroke="{}" stroke-width="0.3" stroke-linejoin="round" stroke-linecap="round"/>"#,
+                points.join(" "),
+                hex(*colour)
+            )?;
+        }
+        writeln!(out, "</svg>")
+    }
+
+    /**
+     * Write to `path`, picking the format from its extension. An unknown
+     * extension fails before anything is written.
+     */
+    pub(crate) fn save<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        let format = match path.extension().and_then(|e| e.to_str()) {
+            Some("ppm") => Format::Ppm,
+            Some("png") => Format::Png,
+            Some("svg") => Format::Svg,
+            _ => anyhow::bail!("unknown image format: {}", path.display()),
+        };
+        let mut out = BufWriter::new(File::create(path)?);
+        match format {
+            Format::Ppm => self.write_ppm(&mut out)?,
+            Format::Png => self.write_png(&mut out)?,
+            Format::Svg => self.write_svg(&mut out)?,
+        }
+        out.flush()?;
+        Ok(())
+    }
+}
+
+enum Format {
+    Ppm,
+    Png,
+    Svg,
+}
+
+fn hex([r, g, b]: Rgb) -> String {
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+/**
+ * Bresenham's line from `a` to `b`, both ends included
+ */
+fn line<F: FnMut(i64, i64)>(a: (i64, i64), b: (i64, i64), mut plot: F) {
+    let (mut x, mut y) = a;
+    let (dx, dy) = ((b.0 - x).abs(), -(b.1 - y).abs());
+    let (sx, sy) = ((b.0 - x).signum(), (b.1 - y).signum());
+    let mut err = dx + dy;
+    loop {
+        plot(x, y);
+        if (x, y) == b {
+            break;
+        }
+        let e2 = 2 * err;
+        if e2 >= dy {
+            err += dy;
+            x += sx;
+        }
+        if e2 <= dx {
+            err += dx;
+            y += sy;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    fn checks() -> Image {
+        let grid: Grid<bool> = Grid::new_from(3, 2, |x, y| (x + y) % 2 == 0);
+        Image::new(&grid, |&b| if b { WHITE } else { BLACK })
+    }
+
+    #[test]
+    fn test_ppm() {
+        let mut out = vec![];
+        checks().write_ppm(&mut out).unwrap();
+        let header = b"P6\n3 2\n255\n";
+        assert_eq!(&out[..header.len()], header);
+        assert_eq!(out.len(), header.len() + 3 * 2 * 3);
+        assert_eq!(
+            &out[header.len()..header.len() + 6],
+            &[255, 255, 255, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn test_scale_and_path() {
+        let image = checks().scale(4).path([(0, 0), (2, 0), (2, 1)], RED);
+        assert_eq!((image.pixel_width(), image.pixel_height()), (12, 8));
+        let pixels = image.pixels();
+        // centre of each cell on the path, and the line between them
+        assert_eq!(pixels[2 * 12 + 2], RED);
+        assert_eq!(pixels[2 * 12 + 6], RED);
+        assert_eq!(pixels[6 * 12 + 10], RED);
+        // off the path the cells keep their colour
+        assert_eq!(pixels[6 * 12 + 2], BLACK);
+        assert_eq!(pixels[6 * 12 + 6], WHITE);
+    }
+
+    #[test]
+    fn test_png_round_trip() {
+        let image = checks().scale(2).path([(1, 1)], YELLOW);
+        let mut out = vec![];
+        image.write_png(&mut out).unwrap();
+
+        let mut reader = png::Decoder::new(&out[..]).read_info().unwrap();
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buf).unwrap();
+        assert_eq!((info.width, info.height), (6, 4));
+        assert_eq!(&buf[..info.buffer_size()], &image.pixels().concat()[..]);
+    }
+
+    #[test]
+    fn test_svg() {
+        let mut out = vec![];
+        checks()
+            .path([(0, 0), (1, 1)], RED)
+            .write_svg(&mut out)
+            .unwrap();
+        let svg = String::from_utf8(out).unwrap();
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.trim_end().ends_with("</svg>"));
+        assert_eq!(svg.matches("<rect").count(), 6);
+        assert!(svg.contains(r#"points="0.5,0.5 1.5,1.5""#));
+        assert!(svg.contains("#dc2828"));
+    }
+
+    #[test]
+    fn test_save_unknown_format() {
+        let path = std::env::temp_dir().join(format!("render_test_{}.jpg", std::process::id()));
+        std::fs::write(&path, b"keep me").unwrap();
+        assert!(checks().save(&path).is_err());
+        assert_eq!(std::fs::read(&path).unwrap(), b"keep me");
+        std::fs::remove_file(&path).unwrap();
+
+        let missing = path.with_extension("bmp");
+        assert!(checks().save(&missing).is_err());
+        assert!(!missing.exists());
+    }
+}
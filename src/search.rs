@@ -0,0 +1,297 @@
+use num_traits::Zero;
+use std::{
+    cmp::Reverse,
+    collections::{hash_map::Entry, BinaryHeap, HashMap, VecDeque},
+    hash::Hash,
+    ops::Add,
+};
+
+/**
+ * The cheapest way found to a goal: its total cost and every state visited
+ * along the way, starting with the start state and ending with the goal.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Path<S, C> {
+    pub(crate) cost: C,
+    pub(crate) states: Vec<S>,
+}
+
+impl<S, C> Path<S, C> {
+    #[cfg(test)]
+    pub(crate) fn goal(&self) -> &S {
+        self.states.last().unwrap()
+    }
+}
+
+const NO_PARENT: usize = usize::MAX;
+
+/**
+ * Every state the search has reached, with the cheapest known cost and the
+ * index of the state it was reached from. States are interned so the heap
+ * and the parent links only need to carry indices.
+ */
+struct Visited<S, C> {
+    index: HashMap<S, usize>,
+    nodes: Vec<(S, usize, C)>,
+}
+
+impl<S: Clone + Eq + Hash, C: Copy> Visited<S, C> {
+    fn new(start: S, cost: C) -> Self {
+        let mut index = HashMap::new();
+        index.insert(start.clone(), 0);
+        Self {
+            index,
+            nodes: vec![(start, NO_PARENT, cost)],
+        }
+    }
+
+    fn path_to(&self, mut idx: usize) -> Path<S, C> {
+        let cost = self.nodes[idx].2;
+        let mut states = vec![];
+        while idx != NO_PARENT {
+            states.push(self.nodes[idx].0.clone());
+            idx = self.nodes[idx].1;
+        }
+        states.reverse();
+        Path { cost, states }
+    }
+}
+
+/**
+ * Breadth-first search where every move costs one step. Stops at the first
+ * state for which `is_goal` returns true.
+ */
+#[allow(dead_code)]
+pub(crate) fn bfs<S, FN, IN, FG>(
+    start: S,
+    mut successors: FN,
+    mut is_goal: FG,
+) -> Option<Path<S, usize>>
+where
+    S: Clone + Eq + Hash,
+    FN: FnMut(&S) -> IN,
+    IN: IntoIterator<Item = S>,
+    FG: FnMut(&S) -> bool,
+{
+    let mut visited = Visited::new(start, 0);
+    let mut q: VecDeque<usize> = VecDeque::from([0]);
+
+    while let Some(idx) = q.pop_front() {
+        let (state, _, steps) = visited.nodes[idx].clone();
+        if is_goal(&state) {
+            return Some(visited.path_to(idx));
+        }
+        for next in successors(&state) {
+            if let Entry::Vacant(e) = visited.index.entry(next) {
+                let next_idx = visited.nodes.len();
+                visited.nodes.push((e.key().clone(), idx, steps + 1));
+                e.insert(next_idx);
+                q.push_back(next_idx);
+            }
+        }
+    }
+    None
+}
+
+/**
+ * Number of steps from `start` to every reachable state
+ */
+pub(crate) fn bfs_distances<S, FN, IN>(start: S, mut successors: FN) -> HashMap<S, usize>
+where
+    S: Clone + Eq + Hash,
+    FN: FnMut(&S) -> IN,
+    IN: IntoIterator<Item = S>,
+{
+    let mut distances: HashMap<S, usize> = Default::default();
+    let mut q: VecDeque<(S, usize)> = Default::default();
+    distances.insert(start.clone(), 0);
+    q.push_back((start, 0));
+
+    while let Some((state, dist)) = q.pop_front() {
+        for next in successors(&state) {
+            if let Entry::Vacant(e) = distances.entry(next.clone()) {
+                e.insert(dist + 1);
+                q.push_back((next, dist + 1));
+            }
+        }
+    }
+    distances
+}
+
+/**
+ * Lowest-cost search. `successors` yields each neighbouring state along with
+ * the cost of moving there; costs must not be negative.
+ */
+pub(crate) fn dijkstra<S, C, FN, IN, FG>(
+    start: S,
+    successors: FN,
+    is_goal: FG,
+) -> Option<Path<S, C>>
+where
+    S: Clone + Eq + Hash,
+    C: Copy + Ord + Zero + Add<Output = C>,
+    FN: FnMut(&S) -> IN,
+    IN: IntoIterator<Item = (S, C)>,
+    FG: FnMut(&S) -> bool,
+{
+    astar(start, successors, |_| C::zero(), is_goal)
+}
+
+/**
+ * Dijkstra guided by `heuristic`, an estimate of the remaining cost from a
+ * state to the nearest goal. The result is only guaranteed to be the cheapest
+ * path if the heuristic never overestimates.
+ */
+pub(crate) fn astar<S, C, FN, IN, FH, FG>(
+    start: S,
+    mut successors: FN,
+    mut heuristic: FH,
+    mut is_goal: FG,
+) -> Option<Path<S, C>>
+where
+    S: Clone + Eq + Hash,
+    C: Copy + Ord + Zero + Add<Output = C>,
+    FN: FnMut(&S) -> IN,
+    IN: IntoIterator<Item = (S, C)>,
+    FH: FnMut(&S) -> C,
+    FG: FnMut(&S) -> bool,
+{
+    let mut heap: BinaryHeap<Reverse<(C, C, usize)>> = BinaryHeap::new();
+    heap.push(Reverse((heuristic(&start), C::zero(), 0)));
+    let mut visited = Visited::new(start, C::zero());
+
+    while let Some(Reverse((_, cost, idx))) = heap.pop() {
+        if cost > visited.nodes[idx].2 {
+            // a cheaper way here was found after this entry was queued
+            continue;
+        }
+        let state = visited.nodes[idx].0.clone();
+        if is_goal(&state) {
+            return Some(visited.path_to(idx));
+        }
+        for (next, step_cost) in successors(&state) {
+            let next_cost = cost + step_cost;
+            let next_idx = match visited.index.entry(next) {
+                Entry::Vacant(e) => {
+                    let next_idx = visited.nodes.len();
+                    visited.nodes.push((e.key().clone(), idx, next_cost));
+                    e.insert(next_idx);
+                    next_idx
+                }
+                Entry::Occupied(e) => {
+                    let next_idx = *e.get();
+                    if next_cost >= visited.nodes[next_idx].2 {
+                        continue;
+                    }
+                    visited.nodes[next_idx].1 = idx;
+                    visited.nodes[next_idx].2 = next_cost;
+                    next_idx
+                }
+            };
+            let estimate = next_cost + heuristic(&visited.nodes[next_idx].0);
+            heap.push(Reverse((estimate, next_cost, next_idx)));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::grid::{Dir, Grid, GridLike};
+
+    fn walls() -> Grid<u8> {
+        #[rustfmt::skip]
+        let rows = [
+            b"..#.....",
+            b"..#.##..",
+            b"..#..#..",
+            b"....#...",
+        ];
+        Grid::new_from(8, 4, |x, y| rows[y][x])
+    }
+
+    fn open_neighbors(g: &Grid<u8>, &(x, y): &(usize, usize)) -> Vec<(usize, usize)> {
+        [Dir::N, Dir::S, Dir::E, Dir::W]
+            .into_iter()
+            .filter_map(|d| g.try_next_coord(x, y, d))
+            .filter(|&(x, y)| *g.get(x, y) == b'.')
+            .collect()
+    }
+
+    #[test]
+    fn test_bfs() {
+        let g = walls();
+        let path = bfs((0, 0), |c| open_neighbors(&g, c), |&c| c == (7, 0)).unwrap();
+        assert_eq!(path.cost, 13);
+        assert_eq!(path.states.len(), 14);
+        assert_eq!(path.states[0], (0, 0));
+        assert_eq!(*path.goal(), (7, 0));
+        assert!(path
+            .states
+            .windows(2)
+            .all(|w| w[0].0.abs_diff(w[1].0) + w[0].1.abs_diff(w[1].1) == 1));
+
+        assert!(bfs((0, 0), |c| open_neighbors(&g, c), |&c| c == (2, 0)).is_none());
+    }
+
+    #[test]
+    fn test_bfs_distances() {
+        let g = walls();
+        let d = bfs_distances((0, 0), |c| open_neighbors(&g, c));
+        assert_eq!(d[&(0, 0)], 0);
+        assert_eq!(d[&(7, 0)], 13);
+        assert!(!d.contains_key(&(2, 0)));
+        assert_eq!(d.len(), g.count_cells(|&c| c == b'.'));
+    }
+
+    #[test]
+    fn test_dijkstra_multiple_goals() {
+        // 0 -> 1 is cheap, 0 -> 2 is expensive, but 2 is also reachable via 1
+        let edges: HashMap<u8, Vec<(u8, u32)>> = [
+            (0, vec![(1, 1), (2, 10)]),
+            (1, vec![(2, 2), (3, 20)]),
+            (2, vec![(3, 1)]),
+            (3, vec![]),
+        ]
+        .into();
+        let path = dijkstra(0, |n| edges[n].clone(), |&n| n == 3).unwrap();
+        assert_eq!(path.cost, 4);
+        assert_eq!(path.states, vec![0, 1, 2, 3]);
+
+        // the first goal reached wins
+        let path = dijkstra(0, |n| edges[n].clone(), |&n| n == 2 || n == 3).unwrap();
+        assert_eq!(path.cost, 3);
+        assert_eq!(*path.goal(), 2);
+    }
+
+    #[test]
+    fn test_astar_matches_dijkstra() {
+        let g: Grid<u32> = Grid::new_from(12, 9, |x, y| ((x * 7 + y * 3) % 9 + 1) as u32);
+        let succ = |&(x, y): &(usize, usize)| {
+            g.cardinal_neighbors(x, y, 0..1)
+                .into_iter()
+                .map(|(x, y, _)| ((x, y), *g.get(x, y)))
+                .collect::<Vec<_>>()
+        };
+        let goal = (g.width - 1, g.height - 1);
+        let d = dijkstra((0, 0), succ, |&c| c == goal).unwrap();
+        let a = astar(
+            (0, 0),
+            succ,
+            |&(x, y)| (goal.0 - x + goal.1 - y) as u32,
+            |&c| c == goal,
+        )
+        .unwrap();
+        assert_eq!(d.cost, a.cost);
+        assert_eq!(
+            a.states
+                .iter()
+                .skip(1)
+                .map(|&(x, y)| *g.get(x, y))
+                .sum::<u32>(),
+            a.cost
+        );
+    }
+}